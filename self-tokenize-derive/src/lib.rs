@@ -16,14 +16,60 @@ extern crate proc_macro;
 extern crate quote;
 extern crate syn;
 
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+
+mod mirror;
 mod util;
 
-#[proc_macro_derive(SelfTokenize)]
-pub fn self_tokenize(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+#[proc_macro_derive(SelfTokenize, attributes(self_tokenize))]
+pub fn self_tokenize(input: TokenStream) -> TokenStream {
     let source = input.to_string();
     let ast = syn::parse_derive_input(&source).unwrap();
-    let expanded = util::expand_macro(&ast, true, true);
-    expanded.parse().unwrap()
+    let mut expanded = util::expand_macro(&ast, true, true);
+    let mut error = None;
+    if util::has_attribute_flag(&ast.attrs, "mirror") {
+        match mirror::expand_mirror(&ast) {
+            Ok(mirror) => expanded.append_all(&[mirror]),
+            Err(message) => error = Some(message)
+        }
+    }
+    let mut output: TokenStream = expanded.parse().unwrap();
+    if let Some(message) = error {
+        output.extend(compile_error(&message, generics_span(input, ast.ident.as_ref())));
+    }
+    output
+}
+
+// syn 0.11 works on the stringified input and keeps no spans, so the generics
+// are found again in the original tokens. Falls back to the derive itself.
+fn generics_span(input: TokenStream, name: &str) -> Span {
+    let mut after_name = false;
+    for token in input {
+        match token {
+            TokenTree::Punct(ref punct) if after_name && punct.as_char() == '<' => return punct.span(),
+            TokenTree::Ident(ref ident) => after_name = ident.to_string() == name,
+            _ => after_name = false
+        }
+    }
+    Span::call_site()
+}
+
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    let mut literal = Literal::string(message);
+    literal.set_span(span);
+    let mut arguments = Group::new(Delimiter::Parenthesis, TokenStream::from(TokenTree::Literal(literal)));
+    arguments.set_span(span);
+    let mut bang = Punct::new('!', Spacing::Alone);
+    bang.set_span(span);
+    let mut semi = Punct::new(';', Spacing::Alone);
+    semi.set_span(span);
+    let tokens = vec![
+        TokenTree::Ident(Ident::new("compile_error", span)),
+        TokenTree::Punct(bang),
+        TokenTree::Group(arguments),
+        TokenTree::Punct(semi)
+    ];
+    tokens.into_iter().collect()
 }
 
 #[proc_macro_derive(DefaultQuote)]
@@ -0,0 +1,262 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use quote;
+use syn;
+
+use util::get_ident_for_index;
+
+pub fn get_mirror_ident(ident: &syn::Ident) -> syn::Ident {
+    syn::Ident::from(format!("{}Static", ident))
+}
+
+/// Fails with a message for `compile_error!` on generic types, whose
+/// parameters could not be mapped to their `'static` mirrors.
+pub fn expand_mirror(ast: &syn::DeriveInput) -> Result<quote::Tokens, String> {
+    if !ast.generics.lifetimes.is_empty() || !ast.generics.ty_params.is_empty() {
+        return Err(format!("`#[self_tokenize(mirror)]` is not supported on generic type `{}`", ast.ident));
+    }
+
+    let name = &ast.ident;
+    let mirror_name = get_mirror_ident(name);
+    let vis = &ast.vis;
+
+    let (mirror_type, to_static_tokens_body, from_static_body) = match ast.body {
+        syn::Body::Struct(ref data) => (
+            expand_mirror_struct_type(vis, &mirror_name, data),
+            expand_struct_to_static_tokens(&mirror_name, data),
+            expand_struct_from_static(name, data)
+        ),
+        syn::Body::Enum(ref variants) => (
+            expand_mirror_enum_type(vis, &mirror_name, variants),
+            expand_enum_to_static_tokens(name, &mirror_name, variants),
+            expand_enum_from_static(name, &mirror_name, variants)
+        )
+    };
+
+    Ok(quote! {
+        #mirror_type
+
+        impl ::self_tokenize_trait::StaticMirror for #name {
+            type Static = #mirror_name;
+
+            fn to_static_tokens(&self, ctx: &mut ::self_tokenize_trait::Context) {
+                #to_static_tokens_body
+            }
+
+            fn from_static(value: &#mirror_name) -> Self {
+                #from_static_body
+            }
+        }
+
+        impl<'a> ::std::convert::From<&'a #mirror_name> for #name {
+            fn from(value: &'a #mirror_name) -> Self {
+                ::self_tokenize_trait::StaticMirror::from_static(value)
+            }
+        }
+    })
+}
+
+pub fn expand_mirror_struct_type(vis: &syn::Visibility, mirror_name: &syn::Ident, data: &syn::VariantData) -> quote::Tokens {
+    let fields = expand_mirror_fields(data);
+
+    match *data {
+        syn::VariantData::Unit => quote! { #vis struct #mirror_name; },
+        syn::VariantData::Tuple(_) => quote! { #vis struct #mirror_name #fields; },
+        syn::VariantData::Struct(_) => quote! { #vis struct #mirror_name #fields }
+    }
+}
+
+pub fn expand_mirror_enum_type(vis: &syn::Visibility, mirror_name: &syn::Ident, variants: &[syn::Variant]) -> quote::Tokens {
+    let variants_iterator = variants.iter().map(|variant| {
+        let variant_name = &variant.ident;
+        let fields = expand_mirror_fields(&variant.data);
+        quote! { #variant_name #fields }
+    });
+
+    quote! {
+        #vis enum #mirror_name {
+            #( #variants_iterator ),*
+        }
+    }
+}
+
+pub fn expand_mirror_fields(data: &syn::VariantData) -> quote::Tokens {
+    let fields_iterator = data.fields().iter().map(|field| {
+        let field_vis = &field.vis;
+        let field_ty = &field.ty;
+        let field_mirror_ty = quote! { <#field_ty as ::self_tokenize_trait::StaticMirror>::Static };
+        match field.ident {
+            Some(ref field_name) => quote! { #field_vis #field_name: #field_mirror_ty },
+            None => quote! { #field_vis #field_mirror_ty }
+        }
+    });
+
+    match *data {
+        syn::VariantData::Unit => quote!{},
+        syn::VariantData::Tuple(_) => quote! { ( #( #fields_iterator ),* ) },
+        syn::VariantData::Struct(_) => quote! { { #( #fields_iterator ),* } }
+    }
+}
+
+pub fn expand_struct_to_static_tokens(mirror_name: &syn::Ident, data: &syn::VariantData) -> quote::Tokens {
+    let mirror_name_str = mirror_name.to_string();
+    let fields_tokenizer = expand_fields_to_static_tokens(data, |field_index, field| {
+        let field_name = field_ident_or_index(field_index, field);
+        quote! { &self.#field_name }
+    });
+
+    quote! {
+        ctx.append(#mirror_name_str);
+        #fields_tokenizer
+    }
+}
+
+pub fn expand_enum_to_static_tokens(name: &syn::Ident, mirror_name: &syn::Ident, variants: &[syn::Variant]) -> quote::Tokens {
+    let variants_iterator = variants.iter().map(|variant| {
+        let variant_name = &variant.ident;
+        let ident_str = quote! { #mirror_name::#variant_name }.to_string();
+        let pattern = expand_variant_pattern(&variant.data);
+        let fields_tokenizer = expand_fields_to_static_tokens(&variant.data, |field_index, field| {
+            let field_name = field_binding(field_index, field);
+            quote! { #field_name }
+        });
+
+        quote! {
+            &#name::#variant_name #pattern => {
+                ctx.append(#ident_str);
+                #fields_tokenizer
+            },
+        }
+    });
+
+    quote! {
+        match self {
+            #( #variants_iterator )*
+        }
+    }
+}
+
+pub fn expand_fields_to_static_tokens<F>(data: &syn::VariantData, accessor: F) -> quote::Tokens
+where
+    F: Fn(usize, &syn::Field) -> quote::Tokens
+{
+    let fields_iterator = data.fields().iter().enumerate().map(|(field_index, field)| {
+        let field_accessor = accessor(field_index, field);
+        let field_key = match field.ident {
+            Some(ref field_name) => {
+                let field_name_str = field_name.to_string();
+                quote! {
+                    ctx.append(#field_name_str);
+                    ctx.append(":");
+                }
+            }
+            None => quote!{}
+        };
+
+        quote! {
+            #field_key
+            ::self_tokenize_trait::StaticMirror::to_static_tokens(#field_accessor, ctx);
+            ctx.append(",");
+        }
+    });
+
+    match *data {
+        syn::VariantData::Unit => quote!{},
+        syn::VariantData::Tuple(_) => quote! {
+            ctx.append("(");
+            #( #fields_iterator )*
+            ctx.append(")");
+        },
+        syn::VariantData::Struct(_) => quote! {
+            ctx.append("{");
+            #( #fields_iterator )*
+            ctx.append("}");
+        }
+    }
+}
+
+pub fn expand_struct_from_static(name: &syn::Ident, data: &syn::VariantData) -> quote::Tokens {
+    let fields = expand_fields_from_static(data, |field_index, field| {
+        let field_name = field_ident_or_index(field_index, field);
+        quote! { &value.#field_name }
+    });
+
+    quote! { #name #fields }
+}
+
+pub fn expand_enum_from_static(name: &syn::Ident, mirror_name: &syn::Ident, variants: &[syn::Variant]) -> quote::Tokens {
+    let variants_iterator = variants.iter().map(|variant| {
+        let variant_name = &variant.ident;
+        let pattern = expand_variant_pattern(&variant.data);
+        let fields = expand_fields_from_static(&variant.data, |field_index, field| {
+            let field_name = field_binding(field_index, field);
+            quote! { #field_name }
+        });
+
+        quote! {
+            &#mirror_name::#variant_name #pattern => #name::#variant_name #fields,
+        }
+    });
+
+    quote! {
+        match value {
+            #( #variants_iterator )*
+        }
+    }
+}
+
+pub fn expand_fields_from_static<F>(data: &syn::VariantData, accessor: F) -> quote::Tokens
+where
+    F: Fn(usize, &syn::Field) -> quote::Tokens
+{
+    let fields_iterator = data.fields().iter().enumerate().map(|(field_index, field)| {
+        let field_accessor = accessor(field_index, field);
+        let field_value = quote! { ::self_tokenize_trait::StaticMirror::from_static(#field_accessor) };
+        match field.ident {
+            Some(ref field_name) => quote! { #field_name: #field_value },
+            None => field_value
+        }
+    });
+
+    match *data {
+        syn::VariantData::Unit => quote!{},
+        syn::VariantData::Tuple(_) => quote! { ( #( #fields_iterator ),* ) },
+        syn::VariantData::Struct(_) => quote! { { #( #fields_iterator ),* } }
+    }
+}
+
+pub fn expand_variant_pattern(data: &syn::VariantData) -> quote::Tokens {
+    let bindings_iterator = data.fields()
+        .iter()
+        .enumerate()
+        .map(|(field_index, field)| field_binding(field_index, field));
+
+    match *data {
+        syn::VariantData::Unit => quote!{},
+        syn::VariantData::Tuple(_) => quote! { ( #( ref #bindings_iterator ),* ) },
+        syn::VariantData::Struct(_) => quote! { { #( ref #bindings_iterator ),* } }
+    }
+}
+
+pub fn field_ident_or_index(field_index: usize, field: &syn::Field) -> syn::Ident {
+    match field.ident {
+        Some(ref field_name) => field_name.clone(),
+        None => syn::Ident::from(field_index)
+    }
+}
+
+pub fn field_binding(field_index: usize, field: &syn::Field) -> syn::Ident {
+    match field.ident {
+        Some(ref field_name) => field_name.clone(),
+        None => get_ident_for_index(field_index)
+    }
+}
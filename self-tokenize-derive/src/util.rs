@@ -15,6 +15,16 @@ use syn;
 pub fn generate_impl(ast: &syn::DeriveInput, to_tokens_impl: bool, to_custom_tokens_impl: bool, body: quote::Tokens) -> quote::Tokens {
     let name = &ast.ident;
     let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let type_body = expand_type(ast);
+    let heap_direct_body = if has_attribute_flag(&ast.attrs, "heap") {
+        quote! {
+            fn is_heap_direct() -> bool {
+                true
+            }
+        }
+    } else {
+        quote!{}
+    };
 
     let to_tokens_impl_body = if to_tokens_impl {
         quote! {
@@ -31,9 +41,19 @@ pub fn generate_impl(ast: &syn::DeriveInput, to_tokens_impl: bool, to_custom_tok
     let to_custom_tokens_impl_body = if to_custom_tokens_impl {
         quote! {
             impl #impl_generics ::self_tokenize_trait::ToCustomTokens for #name #ty_generics #where_clause {
-                fn to_custom_tokens(&self, tokens: &mut ::self_tokenize_trait::Tokens) {
+                fn to_custom_tokens_with(&self, ctx: &mut ::self_tokenize_trait::Context) {
                     #body
                 }
+
+                fn to_type_tokens(ctx: &mut ::self_tokenize_trait::Context) {
+                    #type_body
+                }
+
+                fn impl_name() -> &'static str {
+                    "#[derive(SelfTokenize)]"
+                }
+
+                #heap_direct_body
             }
         }
     } else {
@@ -46,6 +66,49 @@ pub fn generate_impl(ast: &syn::DeriveInput, to_tokens_impl: bool, to_custom_tok
     }
 }
 
+pub fn expand_type(ast: &syn::DeriveInput) -> quote::Tokens {
+    let name_str = ast.ident.to_string();
+
+    if ast.generics.lifetimes.is_empty() && ast.generics.ty_params.is_empty() {
+        return quote! {
+            ctx.append(#name_str);
+        };
+    }
+
+    // Values are emitted as literals that borrow nothing, so every lifetime
+    // can be `'static`.
+    let lifetimes_iterator = ast.generics.lifetimes.iter().map(|_| {
+        quote! {
+            ctx.append("'static");
+        }
+    });
+
+    let ty_params_iterator = ast.generics.ty_params.iter().map(|ty_param| {
+        let ty_param_name = &ty_param.ident;
+        quote! {
+            ctx.append_type::<#ty_param_name>();
+        }
+    });
+
+    let arguments_iterator = lifetimes_iterator.chain(ty_params_iterator).enumerate().map(|(index, argument)| {
+        if index == 0 {
+            argument
+        } else {
+            quote! {
+                ctx.append(",");
+                #argument
+            }
+        }
+    });
+
+    quote! {
+        ctx.append(#name_str);
+        ctx.append("<");
+        #( #arguments_iterator )*
+        ctx.append(">");
+    }
+}
+
 pub fn expand_macro(ast: &syn::DeriveInput, to_tokens_impl: bool, to_custom_tokens_impl: bool) -> quote::Tokens {
     match ast.body {
         syn::Body::Struct(ref data) => expand_struct(ast, data, to_tokens_impl, to_custom_tokens_impl),
@@ -70,7 +133,7 @@ pub fn expand_unit_struct(ast: &syn::DeriveInput, to_tokens_impl: bool, to_custo
         to_tokens_impl,
         to_custom_tokens_impl,
         quote! {
-            tokens.append(#struct_name_str);
+            ctx.append(#struct_name_str);
         }
     )
 }
@@ -90,10 +153,10 @@ pub fn expand_tuple_struct(
         to_tokens_impl,
         to_custom_tokens_impl,
         quote! {
-            tokens.append(#struct_name_str);
-            tokens.append("(");
+            ctx.append(#struct_name_str);
+            ctx.append("(");
             #fields_tokenizer
-            tokens.append(")");
+            ctx.append(")");
         }
     )
 }
@@ -113,10 +176,10 @@ pub fn expand_struct_struct(
         to_tokens_impl,
         to_custom_tokens_impl,
         quote! {
-            tokens.append(#struct_name_str);
-            tokens.append("{");
+            ctx.append(#struct_name_str);
+            ctx.append("{");
             #fields_tokenizer
-            tokens.append("}");
+            ctx.append("}");
         }
     )
 }
@@ -179,7 +242,7 @@ pub fn expand_match_enum_unit_variant(struct_name: &syn::Ident, variant_name: &s
 
     quote! {
         &#ident => {
-            tokens.append(#ident_str);
+            ctx.append(#ident_str);
         },
     }
 }
@@ -200,10 +263,10 @@ pub fn expand_match_enum_tuple_variant(struct_name: &syn::Ident, variant_name: &
 
     quote! {
         &#ident( #(ref #fields_ident_iterator),* ) => {
-            tokens.append(#ident_str);
-            tokens.append("(");
+            ctx.append(#ident_str);
+            ctx.append("(");
             #( #fields_tokenizer_iterator )*
-            tokens.append(")");
+            ctx.append(")");
         },
     }
 }
@@ -230,10 +293,10 @@ pub fn expand_match_enum_struct_variant(struct_name: &syn::Ident, variant_name:
 
     quote! {
         &#ident { #(ref #fields_ident_iterator),* } => {
-            tokens.append(#ident_str);
-            tokens.append("{");
+            ctx.append(#ident_str);
+            ctx.append("{");
             #( #fields_tokenizer_iterator )*
-            tokens.append("}");
+            ctx.append("}");
         },
     }
 }
@@ -244,7 +307,7 @@ pub fn expand_struct_struct_named_field_key_and_value(field_index: usize, fields
 
     quote! {
         #field_key_tokenizer
-        tokens.append(":");
+        ctx.append(":");
         #field_value_tokenizer
     }
 }
@@ -258,7 +321,7 @@ pub fn expand_struct_struct_named_field_key(field: &syn::Field) -> quote::Tokens
     let field_name_str = field_name.to_string();
 
     quote! {
-        tokens.append(#field_name_str);
+        ctx.append(#field_name_str);
     }
 }
 
@@ -268,14 +331,16 @@ pub fn expand_struct_struct_named_field_value(field_index: usize, fields_count:
         .as_ref()
         .expect(&format!("Expected identifier for field `{:?}`", field));
 
-    let accessor = quote! { self.#field_name };
-    expand_field_value(field_index, fields_count, &accessor)
+    let field_name_str = field_name.to_string();
+    let accessor = quote! { &self.#field_name };
+    expand_field_value(field_index, fields_count, &field_name_str, &accessor)
 }
 
 pub fn expand_tuple_struct_anonymous_field_value(field_index: usize, fields_count: usize) -> quote::Tokens {
     let field_name = syn::Ident::from(field_index);
-    let accessor = quote! { self.#field_name };
-    expand_field_value(field_index, fields_count, &accessor)
+    let field_name_str = field_index.to_string();
+    let accessor = quote! { &self.#field_name };
+    expand_field_value(field_index, fields_count, &field_name_str, &accessor)
 }
 
 pub fn expand_enum_named_field_value(field_index: usize, fields_count: usize, field: &syn::Field) -> quote::Tokens {
@@ -286,34 +351,45 @@ pub fn expand_enum_named_field_value(field_index: usize, fields_count: usize, fi
 
     let field_name_str = field_name.to_string();
     let accessor = quote! { #field_name };
-    let field_tokenizer = expand_field_value(field_index, fields_count, &accessor);
+    let field_tokenizer = expand_field_value(field_index, fields_count, &field_name_str, &accessor);
 
     quote! {
-        tokens.append(#field_name_str);
-        tokens.append(":");
+        ctx.append(#field_name_str);
+        ctx.append(":");
         #field_tokenizer
     }
 }
 
 pub fn expand_enum_anonymous_field_value(field_index: usize, fields_count: usize) -> quote::Tokens {
     let field_name = get_ident_for_index(field_index);
+    let field_name_str = field_index.to_string();
     let accessor = quote! { #field_name };
-    expand_field_value(field_index, fields_count, &accessor)
+    expand_field_value(field_index, fields_count, &field_name_str, &accessor)
 }
 
-pub fn expand_field_value(field_index: usize, fields_count: usize, accessor: &quote::Tokens) -> quote::Tokens {
+pub fn expand_field_value(field_index: usize, fields_count: usize, field_name: &str, accessor: &quote::Tokens) -> quote::Tokens {
     let separator = if field_index == fields_count - 1 {
         quote!{}
     } else {
-        quote! { tokens.append(","); }
+        quote! { ctx.append(","); }
     };
 
     quote! {
-        #accessor.to_custom_tokens(tokens);
+        ctx.tokenize_field(#field_name, #accessor);
         #separator
     }
 }
 
+pub fn has_attribute_flag(attrs: &[syn::Attribute], flag: &str) -> bool {
+    attrs.iter().any(|attr| match attr.value {
+        syn::MetaItem::List(ref ident, ref items) if ident == "self_tokenize" => items.iter().any(|item| match item {
+            &syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref word)) => word == flag,
+            _ => false
+        }),
+        _ => false
+    })
+}
+
 pub fn get_ident_for_index(i: usize) -> syn::Ident {
     syn::Ident::from(format!("_{}", i))
 }
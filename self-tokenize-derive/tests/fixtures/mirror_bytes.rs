@@ -0,0 +1,3 @@
+// @generated by spec.rs. Do not edit.
+use super :: MyMirrorBytesStatic ;
+pub static BYTES : MyMirrorBytesStatic = MyMirrorBytesStatic { boxed : & { b"\x01\x02" } , shared : & { b"\x03" } , counted : & { Some ( & [ ] ) } , } ;
@@ -0,0 +1,10 @@
+// @generated by spec.rs. Do not edit.
+pub fn tuple ( ) -> ( :: std :: option :: Option < :: std :: string :: String > , :: std :: vec :: Vec < :: std :: option :: Option < u8 >> , :: std :: collections :: BTreeMap < u8 , :: std :: option :: Option < u8 >> , ) { ( ::std::option::Option::None :: < ::std::string::String > , ::std::vec::Vec :: < ::std::option::Option < u8 > > :: new ( ) , < ::std::collections::BTreeMap < u8 , ::std::option::Option < u8 > > as ::std::iter::FromIterator < _ > > :: from_iter ( ::std::vec! [ ( 1u8 , ::std::option::Option::None :: < u8 > , ) , ] ) , ) }
+pub fn cow ( ) -> :: std :: borrow :: Cow < 'static , str > { < ::std::borrow::Cow < _ > as ::std::convert::From < _ > > :: from ( "foo" ) }
+pub fn typed_cow ( ) -> :: std :: borrow :: Cow < 'static , str > { < ::std::borrow::Cow < str > as ::std::convert::From < _ > > :: from ( "foo" ) }
+pub fn float ( ) -> :: ordered_float :: OrderedFloat < f32 > { ::ordered_float::OrderedFloat ( 1.5f32 ) }
+pub fn typed_float ( ) -> :: ordered_float :: OrderedFloat < f32 > { < ::ordered_float::OrderedFloat < f32 > as ::std::convert::From < f32 > > :: from ( 1.5f32 ) }
+pub fn map ( ) -> :: std :: collections :: HashMap < u8 , :: std :: string :: String > { < ::std::collections::HashMap < _ , _ > as ::std::iter::FromIterator < _ > > :: from_iter ( ::std::vec! [ ( 1u8 , ::std::string::ToString :: to_string ( "a" ) , ) , ] ) }
+pub fn typed_map ( ) -> :: std :: collections :: HashMap < u8 , :: std :: string :: String > { < ::std::collections::HashMap < u8 , ::std::string::String > as ::std::iter::FromIterator < _ > > :: from_iter ( ::std::vec! [ ( 1u8 , ::std::string::ToString :: to_string ( "a" ) , ) , ] ) }
+pub fn modern_map ( ) -> :: std :: collections :: HashMap < u8 , :: std :: string :: String > { < ::std::collections::HashMap < _ , _ > as ::std::convert::From < [ _ ; 1 ] > > :: from ( [ ( 1u8 , < ::std::string::String as ::std::convert::From < & str > > :: from ( "a" ) , ) , ] ) }
+pub fn boxed ( ) -> :: std :: boxed :: Box < :: std :: option :: Option < i8 >> { ::std::boxed::Box :: new ( ::std::option::Option::Some ( ( -1i8 ) ) ) }
@@ -0,0 +1,22 @@
+// @generated by spec.rs. Do not edit.
+pub fn ordered_float_method ( ) -> bool { ::ordered_float::OrderedFloat ( ( -42.5f32 ) ) . is_nan ( ) }
+pub fn ordered_float_index ( ) -> f32 { [ 0f32 , ::ordered_float::OrderedFloat ( ( -42.5f32 ) ) . 0 ] [ 1 ] }
+pub fn ordered_float_cast ( ) -> i32 { ::ordered_float::OrderedFloat ( ( -42.5f32 ) ) . 0 as i32 }
+pub fn cow_method ( ) -> usize { ::std::borrow::Cow :: from ( "foo" ) . len ( ) }
+pub fn cow_index ( ) -> bool { & ::std::borrow::Cow :: from ( "foo" ) [ 1 .. ] == "oo" }
+pub fn cow_cast ( ) -> u64 { ::std::borrow::Cow :: from ( "foo" ) . len ( ) as u64 }
+pub fn arc_method ( ) -> i64 { ::std::sync::Arc :: new ( ( -7i64 ) ) . abs ( ) }
+pub fn arc_index ( ) -> u8 { ::std::sync::Arc :: new ( b"\x01\x02\x03" . to_vec ( ) ) [ 1 ] }
+pub fn arc_cast ( ) -> i32 { * ::std::sync::Arc :: new ( ( -7i64 ) ) as i32 }
+pub fn small_vec_method ( ) -> usize { ::smallvec::SmallVec :: from_buf ( ( * b"\x01\x02\x03\x04" ) ) . len ( ) }
+pub fn small_vec_index ( ) -> u8 { ::smallvec::SmallVec :: from_buf ( ( * b"\x01\x02\x03\x04" ) ) [ 3 ] }
+pub fn small_vec_cast ( ) -> u32 { ::smallvec::SmallVec :: from_buf ( ( * b"\x01\x02\x03\x04" ) ) [ 0 ] as u32 }
+pub fn spilled_small_vec_method ( ) -> bool { ::smallvec::SmallVec :: < [ i16 ; 2 ] > :: from_vec ( vec! [-1, 2, 3] ) . spilled ( ) }
+pub fn spilled_small_vec_index ( ) -> i16 { ::smallvec::SmallVec :: < [ i16 ; 2 ] > :: from_vec ( vec! [-1, 2, 3] ) [ 0 ] }
+pub fn spilled_small_vec_cast ( ) -> i32 { ::smallvec::SmallVec :: < [ i16 ; 2 ] > :: from_vec ( vec! [-1, 2, 3] ) [ 0 ] as i32 }
+pub fn map_method ( ) -> usize { < ::std::collections::HashMap < _ , _ > as ::std::iter::FromIterator < _ > > :: from_iter ( vec! [ ( 1u8 , ( -1i8 ) , ) , ] ) . len ( ) }
+pub fn map_index ( ) -> i8 { < ::std::collections::HashMap < _ , _ > as ::std::iter::FromIterator < _ > > :: from_iter ( vec! [ ( 1u8 , ( -1i8 ) , ) , ] ) [ & 1 ] }
+pub fn map_cast ( ) -> i32 { < ::std::collections::HashMap < _ , _ > as ::std::iter::FromIterator < _ > > :: from_iter ( vec! [ ( 1u8 , ( -1i8 ) , ) , ] ) [ & 1 ] as i32 }
+pub fn tuple_method ( ) -> i8 { ( ( -3i8 ) , "ab" , ) . 0 . abs ( ) }
+pub fn tuple_index ( ) -> u8 { ( ( -3i8 ) , "ab" , ) . 1 . as_bytes ( ) [ 1 ] }
+pub fn tuple_cast ( ) -> i32 { ( ( -3i8 ) , "ab" , ) . 0 as i32 }
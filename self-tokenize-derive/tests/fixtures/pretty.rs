@@ -0,0 +1,51 @@
+// @generated by spec.rs. Do not edit.
+pub fn boxed_vec() -> Box<Vec<i64>> {
+    Box::new(
+        vec![
+            -12,
+            -11,
+            -10,
+            -9,
+            -8,
+            -7,
+            -6,
+            -5,
+            -4,
+            -3,
+            -2,
+            -1,
+        ],
+    )
+}
+pub fn boxed_bytes() -> Box<[u8]> {
+    Box::new(
+        (
+            *b"a byte string longer than the line"
+        ),
+    )
+}
+pub fn negative() -> (i64, i64) {
+    (
+        (
+            -1234567890i64
+        ),
+        (
+            -1234567890i64
+        ),
+    )
+}
+pub(super) fn map() -> (::std::collections::BTreeMap<u8, u8>,) {
+    (
+        <::std::collections::BTreeMap<_, _> as ::std::iter::FromIterator<_>>::from_iter(
+            vec![
+                (
+                    1u8,
+                    2u8,
+                ),
+            ],
+        ),
+    )
+}
+pub(super) static BYTES: &[u8] = &(
+    *b"a byte string longer than the line"
+);
@@ -0,0 +1,17 @@
+// @generated by self-tokenize-trait. Do not edit.
+
+#[allow(dead_code, non_upper_case_globals, unused_parens, clippy::all, clippy::pedantic)]
+pub const ORIGIN: MyTupleStructA = MyTupleStructA((-1i32));
+
+#[allow(dead_code, non_upper_case_globals, unused_parens, clippy::all, clippy::pedantic)]
+pub static NAMES: [&str; 2] = ["first", "second"];
+
+#[allow(dead_code, non_upper_case_globals, unused_parens, clippy::all, clippy::pedantic)]
+pub static SIZES: ::std::sync::LazyLock<Vec<MyTupleStructA>> = ::std::sync::LazyLock::new(
+    || vec![MyTupleStructA(12i32), MyTupleStructA(16i32)],
+);
+
+#[allow(dead_code, non_upper_case_globals, unused_parens, clippy::all, clippy::pedantic)]
+pub fn preset() -> (MyTupleStructA, String) {
+    (MyTupleStructA(7i32), String::from("seven"))
+}
@@ -10,20 +10,25 @@ specific language governing permissions and limitations under the License.
 */
 
 #![cfg_attr(feature = "cargo-clippy", allow(blacklisted_name))]
-#![feature(box_syntax)]
 #![feature(proc_macro)]
 
 extern crate num_traits;
 extern crate ordered_float;
 #[macro_use]
 extern crate quote;
+extern crate rayon;
 extern crate self_tokenize_macro;
 extern crate self_tokenize_trait;
 extern crate smallvec;
+extern crate syn;
 
 use std::borrow::Cow;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::io;
 use std::iter::FromIterator;
+use std::path::Path;
 use std::rc::Rc;
 use std::sync::Arc;
 
@@ -32,7 +37,10 @@ use quote::ToTokens;
 use smallvec::SmallVec;
 
 use self_tokenize_macro::SelfTokenize;
-use self_tokenize_trait::ToCustomTokens;
+use self_tokenize_trait::{append_associated_const, append_chunked_module, append_constructor, append_deduplicated, append_default_impl,
+                          append_lazy_static, append_thread_local, estimate_size, pretty_print, size_report, source_map, trace, Budget,
+                          Compressed, Context, Edition, ErrorKind, ItemKind, Limit, Options, OutputSize, PrettyOptions, RustVersion,
+                          SideFiles, SourceFile, StaticMirror, ToCustomTokens, TokenStreamSink, WriteSink};
 
 #[derive(SelfTokenize)]
 struct MyExampleUnit;
@@ -233,6 +241,111 @@ struct MyRcSmallVec(Rc<SmallVec<[u8; 4]>>);
 #[derive(SelfTokenize)]
 struct MyArcSmallVec(Arc<SmallVec<[u8; 4]>>);
 
+#[derive(SelfTokenize)]
+struct MyGenericStruct<'a, T: ToCustomTokens, U: ToCustomTokens> {
+    foo: Vec<T>,
+    bar: Option<&'a U>
+}
+
+#[derive(SelfTokenize, Debug, PartialEq)]
+#[self_tokenize(mirror)]
+struct MyMirrorStruct {
+    name: String,
+    values: Vec<MyMirrorEnum>,
+    lookup: BTreeMap<String, Option<u32>>,
+    boxed: Box<MyMirrorTupleStruct>
+}
+
+#[derive(SelfTokenize, Debug, PartialEq)]
+#[self_tokenize(mirror)]
+enum MyMirrorEnum {
+    Unit,
+    Tuple(i32, String),
+    Named { flag: bool }
+}
+
+#[derive(SelfTokenize, Debug, PartialEq)]
+#[self_tokenize(mirror)]
+pub struct MyMirrorTupleStruct(pub Cow<'static, str>, pub (u8, char));
+
+#[derive(SelfTokenize, Debug, PartialEq)]
+#[self_tokenize(mirror)]
+#[allow(box_vec)]
+struct MyMirrorBytes {
+    boxed: Box<Vec<u8>>,
+    shared: Arc<Vec<u8>>,
+    counted: Rc<Option<Vec<u8>>>
+}
+
+#[derive(SelfTokenize, Debug, PartialEq)]
+struct MyConstStruct<'a> {
+    name: &'a str,
+    bytes: &'a [u8],
+    cow: Cow<'a, str>,
+    list: Vec<u8>,
+    float: Option<OrderedFloat<f32>>
+}
+
+#[derive(SelfTokenize, Debug, Clone, PartialEq)]
+struct MyStyle {
+    color: String,
+    border: Option<String>
+}
+
+#[derive(SelfTokenize, Debug, Clone, Copy)]
+struct MyTagged {
+    tag: u8,
+    id: u32
+}
+
+// Equal whenever the tags are, whatever the ids.
+impl PartialEq for MyTagged {
+    fn eq(&self, other: &Self) -> bool {
+        self.tag == other.tag
+    }
+}
+
+#[derive(SelfTokenize, Debug, PartialEq)]
+#[self_tokenize(heap)]
+struct MyHeapStruct {
+    id: u32,
+    samples: [u16; 4]
+}
+
+// Written by hand, without `to_type_tokens`.
+struct MyHandWritten(u8);
+
+impl ToCustomTokens for MyHandWritten {
+    fn to_custom_tokens_with(&self, ctx: &mut Context) {
+        ctx.append("MyHandWritten");
+        ctx.append("(");
+        ctx.append(format!("{}u8", self.0));
+        ctx.append(")");
+    }
+}
+
+#[derive(SelfTokenize)]
+struct MyNode {
+    value: u32,
+    next: Option<Box<MyNode>>
+}
+
+/// Compares generated code with `tests/fixtures/<name>`, which is `include!`d
+/// below so that the output is compiled too. Run the tests with
+/// `UPDATE_FIXTURES=1` to rewrite the fixtures after changing the output.
+fn check_fixture(name: &str, source: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+    if env::var_os("UPDATE_FIXTURES").is_some() {
+        fs::write(&path, source).unwrap();
+    }
+    assert_eq!(fs::read_to_string(&path).unwrap(), source, "{} is out of date", name);
+}
+
+fn fixture_source<T: ToString>(items: Vec<T>) -> String {
+    let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+    format!("// @generated by spec.rs. Do not edit.\n{}\n", items.join("\n"))
+}
+
 #[test]
 fn test_example_1() {
     let value = MyExampleStruct {
@@ -362,9 +475,10 @@ fn test_derive_tuple_structs_d() {
     assert_eq!(
         t.to_string(),
         "MyTupleStructD ( true , 42i32 , 4.3f64 , \"foo\" , \"bar\" . to_string ( ) , vec! [ MyTupleStructA ( 1i32 ) , MyTupleStructA ( \
-         2i32 ) , MyTupleStructA ( 3i32 ) , ] , ::std::collections::BTreeMap :: from_iter ( vec! [ ( MyTupleStructA ( 42i32 ) , ( \
-         MyTupleStructB ( \"hello\" ) , MyTupleStructC ( \"world\" . to_string ( ) ) , ) , ) , ( MyTupleStructA ( 43i32 ) , ( \
-         MyTupleStructB ( \"hello\" ) , MyTupleStructC ( \"world\" . to_string ( ) ) , ) , ) , ] ) )"
+         2i32 ) , MyTupleStructA ( 3i32 ) , ] , < ::std::collections::BTreeMap < _ , _ > as ::std::iter::FromIterator < _ > > :: \
+         from_iter ( vec! [ ( MyTupleStructA ( 42i32 ) , ( MyTupleStructB ( \"hello\" ) , MyTupleStructC ( \"world\" . to_string ( ) ) , \
+         ) , ) , ( MyTupleStructA ( 43i32 ) , ( MyTupleStructB ( \"hello\" ) , MyTupleStructC ( \"world\" . to_string ( ) ) , ) , ) , ] ) \
+         )"
     );
 }
 
@@ -409,9 +523,10 @@ fn test_derive_tuple_structs_e() {
     assert_eq!(
         t.to_string(),
         "MyTupleStructE ( true , 42i32 , 4.3f64 , \"foo\" , \"bar\" . to_string ( ) , vec! [ MyStructA { foo : 1i32 } , MyStructA { foo : \
-         2i32 } , MyStructA { foo : 3i32 } , ] , ::std::collections::BTreeMap :: from_iter ( vec! [ ( MyStructA { foo : 42i32 } , ( \
-         MyStructB { foo : \"hello\" } , MyStructC { foo : \"world\" . to_string ( ) } , ) , ) , ( MyStructA { foo : 43i32 } , ( \
-         MyStructB { foo : \"hello\" } , MyStructC { foo : \"world\" . to_string ( ) } , ) , ) , ] ) )"
+         2i32 } , MyStructA { foo : 3i32 } , ] , < ::std::collections::BTreeMap < _ , _ > as ::std::iter::FromIterator < _ > > :: \
+         from_iter ( vec! [ ( MyStructA { foo : 42i32 } , ( MyStructB { foo : \"hello\" } , MyStructC { foo : \"world\" . to_string ( ) } \
+         , ) , ) , ( MyStructA { foo : 43i32 } , ( MyStructB { foo : \"hello\" } , MyStructC { foo : \"world\" . to_string ( ) } , ) , ) \
+         , ] ) )"
     );
 }
 
@@ -485,9 +600,10 @@ fn test_derive_structs_d() {
     assert_eq!(
         t.to_string(),
         "MyStructD { a : true , b : 42i32 , c : 4.3f64 , d : \"foo\" , e : \"bar\" . to_string ( ) , f : vec! [ MyStructA { foo : 1i32 } \
-         , MyStructA { foo : 2i32 } , MyStructA { foo : 3i32 } , ] , g : ::std::collections::BTreeMap :: from_iter ( vec! [ ( MyStructA { \
-         foo : 42i32 } , ( MyStructB { foo : \"hello\" } , MyStructC { foo : \"world\" . to_string ( ) } , ) , ) , ( MyStructA { foo : \
-         43i32 } , ( MyStructB { foo : \"hello\" } , MyStructC { foo : \"world\" . to_string ( ) } , ) , ) , ] ) }"
+         , MyStructA { foo : 2i32 } , MyStructA { foo : 3i32 } , ] , g : < ::std::collections::BTreeMap < _ , _ > as \
+         ::std::iter::FromIterator < _ > > :: from_iter ( vec! [ ( MyStructA { foo : 42i32 } , ( MyStructB { foo : \"hello\" } , \
+         MyStructC { foo : \"world\" . to_string ( ) } , ) , ) , ( MyStructA { foo : 43i32 } , ( MyStructB { foo : \"hello\" } , \
+         MyStructC { foo : \"world\" . to_string ( ) } , ) , ) , ] ) }"
     );
 }
 
@@ -518,9 +634,10 @@ fn test_derive_structs_e() {
     assert_eq!(
         t.to_string(),
         "MyStructE { a : true , b : 42i32 , c : 4.3f64 , d : \"foo\" , e : \"bar\" . to_string ( ) , f : vec! [ MyTupleStructA ( 1i32 ) , \
-         MyTupleStructA ( 2i32 ) , MyTupleStructA ( 3i32 ) , ] , g : ::std::collections::BTreeMap :: from_iter ( vec! [ ( MyTupleStructA \
-         ( 42i32 ) , ( MyTupleStructB ( \"hello\" ) , MyTupleStructC ( \"world\" . to_string ( ) ) , ) , ) , ( MyTupleStructA ( 43i32 ) , \
-         ( MyTupleStructB ( \"hello\" ) , MyTupleStructC ( \"world\" . to_string ( ) ) , ) , ) , ] ) }"
+         MyTupleStructA ( 2i32 ) , MyTupleStructA ( 3i32 ) , ] , g : < ::std::collections::BTreeMap < _ , _ > as \
+         ::std::iter::FromIterator < _ > > :: from_iter ( vec! [ ( MyTupleStructA ( 42i32 ) , ( MyTupleStructB ( \"hello\" ) , \
+         MyTupleStructC ( \"world\" . to_string ( ) ) , ) , ) , ( MyTupleStructA ( 43i32 ) , ( MyTupleStructB ( \"hello\" ) , \
+         MyTupleStructC ( \"world\" . to_string ( ) ) , ) , ) , ] ) }"
     );
 }
 
@@ -613,9 +730,9 @@ fn test_derive_enums_d() {
         t.to_string(),
         "vec! [ MyEnumD :: A ( true ) , MyEnumD :: B ( 42i32 ) , MyEnumD :: C ( 4.3f64 ) , MyEnumD :: D ( \"foo\" ) , MyEnumD :: E ( \
          \"bar\" . to_string ( ) ) , MyEnumD :: F ( vec! [ MyEnumA :: Foo ( 1i32 ) , MyEnumA :: Foo ( 2i32 ) , MyEnumA :: Foo ( 3i32 ) , \
-         ] ) , MyEnumD :: G ( ::std::collections::BTreeMap :: from_iter ( vec! [ ( MyEnumA :: Foo ( 42i32 ) , ( MyEnumB :: Foo ( \
-         \"hello\" ) , MyEnumC :: Foo ( \"world\" . to_string ( ) ) , ) , ) , ( MyEnumA :: Foo ( 43i32 ) , ( MyEnumB :: Foo ( \"hello\" ) \
-         , MyEnumC :: Foo ( \"world\" . to_string ( ) ) , ) , ) , ] ) ) , ]"
+         ] ) , MyEnumD :: G ( < ::std::collections::BTreeMap < _ , _ > as ::std::iter::FromIterator < _ > > :: from_iter ( vec! [ ( \
+         MyEnumA :: Foo ( 42i32 ) , ( MyEnumB :: Foo ( \"hello\" ) , MyEnumC :: Foo ( \"world\" . to_string ( ) ) , ) , ) , ( MyEnumA :: \
+         Foo ( 43i32 ) , ( MyEnumB :: Foo ( \"hello\" ) , MyEnumC :: Foo ( \"world\" . to_string ( ) ) , ) , ) , ] ) ) , ]"
     );
 }
 
@@ -667,10 +784,11 @@ fn test_derive_enums_named_d() {
         t.to_string(),
         "vec! [ MyEnumNamedD :: A { foo : true } , MyEnumNamedD :: B { foo : 42i32 } , MyEnumNamedD :: C { foo : 4.3f64 } , MyEnumNamedD \
          :: D { foo : \"foo\" } , MyEnumNamedD :: E { foo : \"bar\" . to_string ( ) } , MyEnumNamedD :: F { foo : vec! [ MyEnumNamedA :: \
-         Foo { foo : 1i32 } , MyEnumNamedA :: Foo { foo : 2i32 } , MyEnumNamedA :: Foo { foo : 3i32 } , ] } , MyEnumNamedD :: G { foo : \
-         ::std::collections::BTreeMap :: from_iter ( vec! [ ( MyEnumNamedA :: Foo { foo : 42i32 } , ( MyEnumNamedB :: Foo { foo : \
-         \"hello\" } , MyEnumNamedC :: Foo { foo : \"world\" . to_string ( ) } , ) , ) , ( MyEnumNamedA :: Foo { foo : 43i32 } , ( \
-         MyEnumNamedB :: Foo { foo : \"hello\" } , MyEnumNamedC :: Foo { foo : \"world\" . to_string ( ) } , ) , ) , ] ) } , ]"
+         Foo { foo : 1i32 } , MyEnumNamedA :: Foo { foo : 2i32 } , MyEnumNamedA :: Foo { foo : 3i32 } , ] } , MyEnumNamedD :: G { foo : < \
+         ::std::collections::BTreeMap < _ , _ > as ::std::iter::FromIterator < _ > > :: from_iter ( vec! [ ( MyEnumNamedA :: Foo { foo : \
+         42i32 } , ( MyEnumNamedB :: Foo { foo : \"hello\" } , MyEnumNamedC :: Foo { foo : \"world\" . to_string ( ) } , ) , ) , ( \
+         MyEnumNamedA :: Foo { foo : 43i32 } , ( MyEnumNamedB :: Foo { foo : \"hello\" } , MyEnumNamedC :: Foo { foo : \"world\" . \
+         to_string ( ) } , ) , ) , ] ) } , ]"
     );
 }
 
@@ -680,7 +798,7 @@ fn test_ordered_float() {
     let mut t = quote::Tokens::new();
     value.to_custom_tokens(&mut t);
 
-    assert_eq!(t.to_string(), "MyOrderedFloat ( 42f32 . into ( ) )");
+    assert_eq!(t.to_string(), "MyOrderedFloat ( ::ordered_float::OrderedFloat ( 42f32 ) )");
 }
 
 #[test]
@@ -701,16 +819,16 @@ fn test_box_1() {
     let mut t = quote::Tokens::new();
     value.to_custom_tokens(&mut t);
 
-    assert_eq!(t.to_string(), "MyBox ( box 1u8 )");
+    assert_eq!(t.to_string(), "MyBox ( ( box 1u8 ) )");
 }
 
 #[test]
 fn test_box_2() {
-    let value = MyBox(box 1u8);
+    let value = MyBox(Box::new(1u8));
     let mut t = quote::Tokens::new();
     value.to_custom_tokens(&mut t);
 
-    assert_eq!(t.to_string(), "MyBox ( box 1u8 )");
+    assert_eq!(t.to_string(), "MyBox ( ( box 1u8 ) )");
 }
 
 #[test]
@@ -719,16 +837,16 @@ fn test_boxed_slice_1() {
     let mut t = quote::Tokens::new();
     value.to_custom_tokens(&mut t);
 
-    assert_eq!(t.to_string(), "MyBoxedSlice ( box [1] )");
+    assert_eq!(t.to_string(), "MyBoxedSlice ( ( box ( * b\"\\x01\" ) ) )");
 }
 
 #[test]
 fn test_boxed_slice_2() {
-    let value = MyBoxedSlice(box [1u8]);
+    let value = MyBoxedSlice(Box::new([1u8]));
     let mut t = quote::Tokens::new();
     value.to_custom_tokens(&mut t);
 
-    assert_eq!(t.to_string(), "MyBoxedSlice ( box [1] )");
+    assert_eq!(t.to_string(), "MyBoxedSlice ( ( box ( * b\"\\x01\" ) ) )");
 }
 
 #[test]
@@ -737,16 +855,16 @@ fn test_boxed_vec_1() {
     let mut t = quote::Tokens::new();
     value.to_custom_tokens(&mut t);
 
-    assert_eq!(t.to_string(), "MyBoxedVec ( box vec! [1] )");
+    assert_eq!(t.to_string(), "MyBoxedVec ( ( box b\"\\x01\" . to_vec ( ) ) )");
 }
 
 #[test]
 fn test_boxed_vec_2() {
-    let value = MyBoxedVec(box vec![1u8]);
+    let value = MyBoxedVec(Box::new(vec![1u8]));
     let mut t = quote::Tokens::new();
     value.to_custom_tokens(&mut t);
 
-    assert_eq!(t.to_string(), "MyBoxedVec ( box vec! [1] )");
+    assert_eq!(t.to_string(), "MyBoxedVec ( ( box b\"\\x01\" . to_vec ( ) ) )");
 }
 
 #[test]
@@ -836,7 +954,7 @@ fn test_smallvec_empty() {
     let mut t = quote::Tokens::new();
     value.to_custom_tokens(&mut t);
 
-    assert_eq!(t.to_string(), "MySmallVec ( SmallVec :: new ( ) )");
+    assert_eq!(t.to_string(), "MySmallVec ( ::smallvec::SmallVec :: new ( ) )");
 }
 
 #[test]
@@ -847,7 +965,7 @@ fn test_smallvec_1() {
 
     assert_eq!(
         t.to_string(),
-        "MySmallVec ( SmallVec :: from_buf ( [1, 2, 3, 4] ) )"
+        "MySmallVec ( ::smallvec::SmallVec :: from_buf ( ( * b\"\\x01\\x02\\x03\\x04\" ) ) )"
     );
 }
 
@@ -859,7 +977,7 @@ fn test_smallvec_2() {
 
     assert_eq!(
         t.to_string(),
-        "MySmallVec ( SmallVec :: from_vec ( vec! [1] ) )"
+        "MySmallVec ( ::smallvec::SmallVec :: < [ u8 ; 4 ] > :: from_vec ( b\"\\x01\" . to_vec ( ) ) )"
     );
 }
 
@@ -871,7 +989,7 @@ fn test_smallvec_3() {
 
     assert_eq!(
         t.to_string(),
-        "MySmallVec ( SmallVec :: from_vec ( vec! [1, 2, 3, 4, 5] ) )"
+        "MySmallVec ( ::smallvec::SmallVec :: < [ u8 ; 4 ] > :: from_vec ( b\"\\x01\\x02\\x03\\x04\\x05\" . to_vec ( ) ) )"
     );
 }
 
@@ -925,6 +1043,99 @@ fn test_arc_4() {
     value.to_custom_tokens(&mut t);
 }
 
+#[test]
+fn test_bytes_round_trip_png() {
+    let value = include_bytes!("fixtures/Quantum.png").to_vec();
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(parse_byte_string_receiver(&t), value);
+}
+
+#[test]
+fn test_bytes_round_trip_ttf() {
+    let value = include_bytes!("fixtures/FreeSans.ttf").to_vec();
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(parse_byte_string_receiver(&t), value);
+}
+
+fn parse_byte_string_receiver(tokens: &quote::Tokens) -> Vec<u8> {
+    decode_bytes_expr(&syn::parse_expr(tokens.as_str()).unwrap())
+}
+
+fn decode_bytes_expr(expr: &syn::Expr) -> Vec<u8> {
+    match expr.node {
+        syn::ExprKind::Lit(syn::Lit::ByteStr(ref bytes, _)) => bytes.clone(),
+        syn::ExprKind::Call(_, ref args) => decode_bytes_expr(&args[0]),
+        syn::ExprKind::MethodCall(ref method, _, ref args) if method == "to_vec" || method == "collect" => decode_bytes_expr(&args[0]),
+        syn::ExprKind::MethodCall(ref method, _, ref args) if method == "chain" => {
+            let mut bytes = decode_bytes_expr(&args[0]);
+            bytes.extend(decode_bytes_expr(&args[1]));
+            bytes
+        }
+        syn::ExprKind::MethodCall(ref method, _, ref args) if method == "take" => match (&args[0].node, &args[1].node) {
+            (&syn::ExprKind::Call(_, ref repeated), &syn::ExprKind::Lit(syn::Lit::Int(len, _))) => match repeated[0].node {
+                syn::ExprKind::Lit(syn::Lit::Int(byte, _)) => vec![byte as u8; len as usize],
+                ref other => panic!("Expected repeated byte, found `{:?}`", other)
+            },
+            other => panic!("Expected `repeat(x).take(n)`, found `{:?}`", other)
+        },
+        ref other => panic!("Expected byte string expression, found `{:?}`", other)
+    }
+}
+
+#[test]
+fn test_bytes_array() {
+    let value = [1u8, b'"', b'\\', b'a', 0xff];
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(t.to_string(), "( * b\"\\x01\\\"\\\\a\\xff\" )");
+    assert_eq!(*b"\x01\"\\a\xff", value);
+}
+
+#[test]
+fn test_bytes_typed_empty() {
+    let value = (Vec::<u8>::new(), [0u8; 0]);
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, Options { typed: true, ..Options::default() }));
+
+    assert_eq!(t.to_string(), "( Vec :: < u8 > :: new ( ) , < [ u8 ; 0 ] as ::std::default::Default > :: default ( ) , )");
+}
+
+#[test]
+fn test_bytes_side_files() {
+    let dir = std::env::temp_dir().join(format!("self-tokenize-{}", std::process::id()));
+    let value = (Arc::new(include_bytes!("fixtures/Quantum.png").to_vec()), vec![1u8, 2]);
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        side_files: Some(SideFiles {
+            threshold: 1024,
+            ..SideFiles::new(&dir)
+        }),
+        ..Options::default()
+    };
+    let mut ctx = Context::with_options(&mut t, options);
+    value.to_custom_tokens_with(&mut ctx);
+
+    let files = ctx.generated_files().to_vec();
+    assert_eq!(ctx.finish(), Ok(()));
+    assert_eq!(files.len(), 1);
+    assert_eq!(std::fs::read(&files[0]).unwrap(), *value.0);
+    assert_eq!(
+        t.to_string(),
+        format!(
+            "( ::std::sync::Arc :: new ( include_bytes! ( concat! ( env! ( \"OUT_DIR\" ) , \"/{}\" ) ) . to_vec ( ) ) , b\"\\x01\\x02\" . \
+             to_vec ( ) , )",
+            files[0].file_name().unwrap().to_str().unwrap()
+        )
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
 #[test]
 fn test_arc_5() {
     let value = Arc::new(vec![0u8; 10]);
@@ -933,7 +1144,7 @@ fn test_arc_5() {
 
     assert_eq!(
         t.to_string(),
-        "::std::sync::Arc :: new ( vec! [0, 0, 0, 0, 0, 0, 0, 0, 0, 0] )"
+        "::std::sync::Arc :: new ( vec! [ 0u8 ; 10 ] )"
     );
 }
 
@@ -942,6 +1153,8 @@ fn test_arc_6() {
     let value = Arc::new(vec![0u8; 100_000_000]);
     let mut t = quote::Tokens::new();
     value.to_custom_tokens(&mut t);
+
+    assert_eq!(t.to_string(), "::std::sync::Arc :: new ( vec! [ 0u8 ; 100000000 ] )");
 }
 
 #[test]
@@ -952,7 +1165,7 @@ fn test_rc_smallvec() {
 
     assert_eq!(
         t.to_string(),
-        "MyRcSmallVec ( ::std::rc::Rc :: new ( SmallVec :: from_buf ( [1, 2, 3, 4] ) ) )"
+        "MyRcSmallVec ( ::std::rc::Rc :: new ( ::smallvec::SmallVec :: from_buf ( ( * b\"\\x01\\x02\\x03\\x04\" ) ) ) )"
     );
 }
 
@@ -964,6 +1177,2318 @@ fn test_arc_smallvec() {
 
     assert_eq!(
         t.to_string(),
-        "MyArcSmallVec ( ::std::sync::Arc :: new ( SmallVec :: from_buf ( [1, 2, 3, 4] ) ) )"
+        "MyArcSmallVec ( ::std::sync::Arc :: new ( ::smallvec::SmallVec :: from_buf ( ( * b\"\\x01\\x02\\x03\\x04\" ) ) ) )"
+    );
+}
+
+#[test]
+fn test_precedence_negative_integer() {
+    let value = MyTupleStructA(-42);
+    let mut t = quote::Tokens::new();
+    value.0.to_custom_tokens(&mut t);
+
+    assert_eq!(quote! { #t.abs() }.to_string(), "( -42i32 ) . abs ( )");
+    assert_eq!(quote! { #t as u8 }.to_string(), "( -42i32 ) as u8");
+    assert_eq!(quote! { [1][#t as usize] }.to_string(), "[ 1 ] [ ( -42i32 ) as usize ]");
+    assert_eq!(quote! { #value }.to_string(), "MyTupleStructA ( ( -42i32 ) )");
+}
+
+#[test]
+fn test_precedence_negative_float() {
+    let value = -4.5f64;
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(quote! { #t.abs() }.to_string(), "( -4.5f64 ) . abs ( )");
+    assert_eq!(quote! { #t as i32 }.to_string(), "( -4.5f64 ) as i32");
+}
+
+#[test]
+fn test_precedence_positive_number() {
+    let value = 42u8;
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(quote! { #t.count_ones() }.to_string(), "42u8 . count_ones ( )");
+}
+
+#[test]
+fn test_precedence_string() {
+    let value = "foo".to_string();
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(quote! { #t.len() }.to_string(), "\"foo\" . to_string ( ) . len ( )");
+    assert_eq!(quote! { &#t[1..] }.to_string(), "& \"foo\" . to_string ( ) [ 1 .. ]");
+    assert_eq!(quote! { -#t.len() as i32 }.to_string(), "- \"foo\" . to_string ( ) . len ( ) as i32");
+}
+
+#[test]
+fn test_precedence_ordered_float() {
+    let value = MyOrderedFloat(OrderedFloat(-42.0));
+    let mut t = quote::Tokens::new();
+    value.0.to_custom_tokens(&mut t);
+
+    assert_eq!(quote! { #t.is_nan() }.to_string(), "::ordered_float::OrderedFloat ( ( -42f32 ) ) . is_nan ( )");
+    assert_eq!(quote! { #value }.to_string(), "MyOrderedFloat ( ::ordered_float::OrderedFloat ( ( -42f32 ) ) )");
+}
+
+#[test]
+fn test_precedence_box() {
+    let value = Box::new(1u8);
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(quote! { #t.count_ones() }.to_string(), "( box 1u8 ) . count_ones ( )");
+    assert_eq!(quote! { *#t as u32 }.to_string(), "* ( box 1u8 ) as u32");
+}
+
+#[test]
+fn test_precedence_boxed_slice() {
+    let value = vec![1u8, 2u8].into_boxed_slice();
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(quote! { #t[0] }.to_string(), "( box ( * b\"\\x01\\x02\" ) ) [ 0 ]");
+    assert_eq!(quote! { #t.len() }.to_string(), "( box ( * b\"\\x01\\x02\" ) ) . len ( )");
+}
+
+#[test]
+fn test_precedence_vec() {
+    let value = vec![MyTupleStructA(-1), MyTupleStructA(2)];
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(
+        quote! { #t[0] }.to_string(),
+        "vec! [ MyTupleStructA ( ( -1i32 ) ) , MyTupleStructA ( 2i32 ) , ] [ 0 ]"
+    );
+    assert_eq!(
+        quote! { #t.len() }.to_string(),
+        "vec! [ MyTupleStructA ( ( -1i32 ) ) , MyTupleStructA ( 2i32 ) , ] . len ( )"
+    );
+}
+
+#[test]
+fn test_precedence_option() {
+    let value = Some(-1i8);
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(quote! { #t.unwrap() }.to_string(), "Some ( ( -1i8 ) ) . unwrap ( )");
+}
+
+#[test]
+fn test_precedence_map() {
+    let value = BTreeMap::from_iter(vec![(-1i32, MyStructA { foo: -2 })]);
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(
+        quote! { #t[&1] }.to_string(),
+        "< ::std::collections::BTreeMap < _ , _ > as ::std::iter::FromIterator < _ > > :: from_iter ( vec! [ ( ( -1i32 ) , MyStructA { \
+         foo : ( -2i32 ) } , ) , ] ) [ & 1 ]"
+    );
+}
+
+#[test]
+fn test_precedence_rc() {
+    let value = Rc::new(-1i64);
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(quote! { #t.abs() }.to_string(), "::std::rc::Rc :: new ( ( -1i64 ) ) . abs ( )");
+}
+
+#[test]
+fn test_precedence_output_compiles() {
+    fn tokens<T: ToCustomTokens>(value: T) -> quote::Tokens {
+        let mut t = quote::Tokens::new();
+        value.to_custom_tokens(&mut t);
+        t
+    }
+
+    let float = tokens(OrderedFloat(-42.5f32));
+    let cow = tokens(Cow::from("foo"));
+    let arc = tokens(Arc::new(-7i64));
+    let arc_vec = tokens(Arc::new(vec![1u8, 2, 3]));
+    let small_vec = tokens(SmallVec::<[u8; 4]>::from_buf([1, 2, 3, 4]));
+    let spilled_small_vec = tokens(SmallVec::<[i16; 2]>::from_vec(vec![-1, 2, 3]));
+    let map = tokens(HashMap::<u8, i8>::from_iter(vec![(1, -1)]));
+    let tuple = tokens((-3i8, "ab"));
+    let source = fixture_source(vec![
+        quote! { pub fn ordered_float_method() -> bool { #float.is_nan() } },
+        quote! { pub fn ordered_float_index() -> f32 { [0f32, #float.0][1] } },
+        quote! { pub fn ordered_float_cast() -> i32 { #float.0 as i32 } },
+        quote! { pub fn cow_method() -> usize { #cow.len() } },
+        quote! { pub fn cow_index() -> bool { &#cow[1..] == "oo" } },
+        quote! { pub fn cow_cast() -> u64 { #cow.len() as u64 } },
+        quote! { pub fn arc_method() -> i64 { #arc.abs() } },
+        quote! { pub fn arc_index() -> u8 { #arc_vec[1] } },
+        quote! { pub fn arc_cast() -> i32 { *#arc as i32 } },
+        quote! { pub fn small_vec_method() -> usize { #small_vec.len() } },
+        quote! { pub fn small_vec_index() -> u8 { #small_vec[3] } },
+        quote! { pub fn small_vec_cast() -> u32 { #small_vec[0] as u32 } },
+        quote! { pub fn spilled_small_vec_method() -> bool { #spilled_small_vec.spilled() } },
+        quote! { pub fn spilled_small_vec_index() -> i16 { #spilled_small_vec[0] } },
+        quote! { pub fn spilled_small_vec_cast() -> i32 { #spilled_small_vec[0] as i32 } },
+        quote! { pub fn map_method() -> usize { #map.len() } },
+        quote! { pub fn map_index() -> i8 { #map[&1] } },
+        quote! { pub fn map_cast() -> i32 { #map[&1] as i32 } },
+        quote! { pub fn tuple_method() -> i8 { #tuple.0.abs() } },
+        quote! { pub fn tuple_index() -> u8 { #tuple.1.as_bytes()[1] } },
+        quote! { pub fn tuple_cast() -> i32 { #tuple.0 as i32 } },
+    ]);
+    check_fixture("precedence.rs", &source);
+
+    assert!(!precedence::ordered_float_method());
+    assert_eq!(precedence::ordered_float_index(), -42.5);
+    assert_eq!(precedence::ordered_float_cast(), -42);
+    assert_eq!(precedence::cow_method(), 3);
+    assert!(precedence::cow_index());
+    assert_eq!(precedence::cow_cast(), 3);
+    assert_eq!(precedence::arc_method(), 7);
+    assert_eq!(precedence::arc_index(), 2);
+    assert_eq!(precedence::arc_cast(), -7);
+    assert_eq!(precedence::small_vec_method(), 4);
+    assert_eq!(precedence::small_vec_index(), 4);
+    assert_eq!(precedence::small_vec_cast(), 1);
+    assert!(precedence::spilled_small_vec_method());
+    assert_eq!(precedence::spilled_small_vec_index(), -1);
+    assert_eq!(precedence::spilled_small_vec_cast(), -1);
+    assert_eq!(precedence::map_method(), 1);
+    assert_eq!(precedence::map_index(), -1);
+    assert_eq!(precedence::map_cast(), -1);
+    assert_eq!(precedence::tuple_method(), 3);
+    assert_eq!(precedence::tuple_index(), b'b');
+    assert_eq!(precedence::tuple_cast(), -3);
+}
+
+#[allow(unused_parens, clippy::double_parens)]
+mod precedence {
+    include!("fixtures/precedence.rs");
+}
+
+#[test]
+fn test_hand_written_impl() {
+    let value = vec![MyHandWritten(1), MyHandWritten(2)];
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(t.to_string(), "vec! [ MyHandWritten ( 1u8 ) , MyHandWritten ( 2u8 ) , ]");
+
+    let mut t = quote::Tokens::new();
+    MyHandWritten(3).to_custom_tokens_with(&mut Context::new(&mut t));
+
+    assert_eq!(t.to_string(), "MyHandWritten ( 3u8 )");
+}
+
+#[test]
+fn test_typed_empty_vec() {
+    let value: Vec<u8> = vec![];
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, Options { typed: true, ..Options::default() }));
+
+    assert_eq!(t.to_string(), "Vec :: < u8 > :: new ( )");
+}
+
+#[test]
+fn test_typed_vec() {
+    let value = vec![vec![1u8, 2u8], vec![]];
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, Options { typed: true, ..Options::default() }));
+
+    assert_eq!(t.to_string(), "vec! [ b\"\\x01\\x02\" . to_vec ( ) , Vec :: < u8 > :: new ( ) , ]");
+}
+
+#[test]
+fn test_typed_none() {
+    let value = MyOptionString(None);
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, Options { typed: true, ..Options::default() }));
+
+    assert_eq!(t.to_string(), "MyOptionString ( None :: < String > )");
+}
+
+#[test]
+fn test_typed_none_cow() {
+    let value = MyOptionCowStr(None);
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, Options { typed: true, ..Options::default() }));
+
+    assert_eq!(t.to_string(), "MyOptionCowStr ( None :: < ::std::borrow::Cow < str > > )");
+}
+
+#[test]
+fn test_typed_cow() {
+    let value = MyCow(Cow::from("test"));
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, Options { typed: true, ..Options::default() }));
+
+    assert_eq!(t.to_string(), "MyCow ( ::std::borrow::Cow :: < str > :: from ( \"test\" ) )");
+}
+
+#[test]
+fn test_typed_empty_maps() {
+    let value: (HashMap<String, Vec<u8>>, BTreeMap<MyTupleStructA, (MyTupleStructB, MyTupleStructC)>) = (HashMap::new(), BTreeMap::new());
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, Options { typed: true, ..Options::default() }));
+
+    assert_eq!(
+        t.to_string(),
+        "( < ::std::collections::HashMap < String , Vec < u8 > > as ::std::iter::FromIterator < _ > > :: from_iter ( vec! [ ] ) , < \
+         ::std::collections::BTreeMap < MyTupleStructA , ( MyTupleStructB < 'static > , MyTupleStructC , ) > as \
+         ::std::iter::FromIterator < _ > > :: from_iter ( vec! [ ] ) , )"
+    );
+}
+
+#[test]
+fn test_typed_ordered_float() {
+    let value = MyOrderedFloat(OrderedFloat(42.0));
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, Options { typed: true, ..Options::default() }));
+
+    assert_eq!(t.to_string(), "MyOrderedFloat ( ::ordered_float::OrderedFloat :: < f32 > :: from ( 42f32 ) )");
+}
+
+#[test]
+fn test_typed_smallvec_empty() {
+    let value = MySmallVec(SmallVec::new());
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, Options { typed: true, ..Options::default() }));
+
+    assert_eq!(t.to_string(), "MySmallVec ( ::smallvec::SmallVec :: < [ u8 ; 4 ] > :: new ( ) )");
+}
+
+#[test]
+fn test_typed_smallvec_spilled() {
+    let value = MySmallVec(SmallVec::from_vec(vec![1, 2, 3, 4, 5]));
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, Options { typed: true, ..Options::default() }));
+
+    assert_eq!(
+        t.to_string(),
+        "MySmallVec ( ::smallvec::SmallVec :: < [ u8 ; 4 ] > :: from_vec ( b\"\\x01\\x02\\x03\\x04\\x05\" . to_vec ( ) ) )"
+    );
+}
+
+#[test]
+fn test_typed_empty_boxed_slice() {
+    let value = MyBoxedSlice(vec![].into_boxed_slice());
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, Options { typed: true, ..Options::default() }));
+
+    assert_eq!(
+        t.to_string(),
+        "MyBoxedSlice ( ( box < [ u8 ; 0 ] as ::std::default::Default > :: default ( ) ) )"
+    );
+}
+
+#[test]
+fn test_typed_empty_array() {
+    let value: [MyStructA; 0] = [];
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, Options { typed: true, ..Options::default() }));
+
+    assert_eq!(t.to_string(), "< [ MyStructA ; 0 ] as ::std::default::Default > :: default ( )");
+}
+
+#[test]
+fn test_typed_generic_struct() {
+    let value: Option<MyGenericStruct<u8, MyStructC>> = None;
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, Options { typed: true, ..Options::default() }));
+
+    assert_eq!(t.to_string(), "None :: < MyGenericStruct < 'static , u8 , MyStructC > >");
+}
+
+#[test]
+fn test_typed_generic_struct_fields() {
+    let value = MyGenericStruct::<u8, MyStructC> { foo: vec![], bar: None };
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, Options { typed: true, ..Options::default() }));
+
+    assert_eq!(
+        t.to_string(),
+        "MyGenericStruct { foo : Vec :: < u8 > :: new ( ) , bar : None :: < & MyStructC > }"
+    );
+}
+
+#[test]
+fn test_untyped_generic_struct_fields() {
+    let value = MyGenericStruct::<u8, MyStructC> { foo: vec![], bar: None };
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(t.to_string(), "MyGenericStruct { foo : vec! [] , bar : None }");
+}
+
+#[test]
+fn test_no_implicit_prelude() {
+    let value = (Some("foo".to_string()), vec![MyTupleStructA(1)], MyOrderedFloat(OrderedFloat(42.0)));
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        no_implicit_prelude: true,
+        ..Options::default()
+    };
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, options));
+
+    assert_eq!(
+        t.to_string(),
+        "( ::std::option::Option::Some ( ::std::string::ToString :: to_string ( \"foo\" ) ) , ::std::vec! [ MyTupleStructA ( 1i32 ) , ] , \
+         MyOrderedFloat ( ::ordered_float::OrderedFloat ( 42f32 ) ) , )"
     );
 }
+
+#[test]
+fn test_no_implicit_prelude_typed() {
+    let value: (Option<String>, Vec<Option<u8>>, BTreeMap<u8, Option<u8>>) =
+        (None, vec![], BTreeMap::from_iter(vec![(1, None)]));
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        typed: true,
+        no_implicit_prelude: true,
+        ..Options::default()
+    };
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, options));
+
+    assert_eq!(
+        t.to_string(),
+        "( ::std::option::Option::None :: < ::std::string::String > , ::std::vec::Vec :: < ::std::option::Option < u8 > > :: new ( ) , < \
+         ::std::collections::BTreeMap < u8 , ::std::option::Option < u8 > > as ::std::iter::FromIterator < _ > > :: from_iter ( \
+         ::std::vec! [ ( 1u8 , ::std::option::Option::None :: < u8 > , ) , ] ) , )"
+    );
+}
+
+#[test]
+fn test_no_implicit_prelude_output_compiles() {
+    fn tokens<T: ToCustomTokens>(value: T, options: &Options) -> quote::Tokens {
+        let mut t = quote::Tokens::new();
+        value.to_custom_tokens_with(&mut Context::with_options(&mut t, options.clone()));
+        t
+    }
+
+    // Targets 2018 so that boxes are emitted with `Box::new`.
+    let options = Options {
+        no_implicit_prelude: true,
+        edition: Edition::Edition2018,
+        ..Options::default()
+    };
+    let typed = Options {
+        typed: true,
+        ..options.clone()
+    };
+    let modern = Options {
+        edition: Edition::Edition2021,
+        ..options.clone()
+    };
+    let tuple: (Option<String>, Vec<Option<u8>>, BTreeMap<u8, Option<u8>>) = (None, vec![], BTreeMap::from_iter(vec![(1, None)]));
+    let tuple = tokens(tuple, &typed);
+    let cow = tokens(Cow::from("foo"), &options);
+    let typed_cow = tokens(Cow::from("foo"), &typed);
+    let float = tokens(OrderedFloat(1.5f32), &options);
+    let typed_float = tokens(OrderedFloat(1.5f32), &typed);
+    let map = tokens(HashMap::<u8, String>::from_iter(vec![(1, "a".to_string())]), &options);
+    let typed_map = tokens(HashMap::<u8, String>::from_iter(vec![(1, "a".to_string())]), &typed);
+    let modern_map = tokens(HashMap::<u8, String>::from_iter(vec![(1, "a".to_string())]), &modern);
+    let boxed = tokens(Box::new(Some(-1i8)), &options);
+    let source = fixture_source(vec![
+        quote! {
+            pub fn tuple() -> (
+                ::std::option::Option<::std::string::String>,
+                ::std::vec::Vec<::std::option::Option<u8>>,
+                ::std::collections::BTreeMap<u8, ::std::option::Option<u8>>,
+            ) { #tuple }
+        },
+        quote! { pub fn cow() -> ::std::borrow::Cow<'static, str> { #cow } },
+        quote! { pub fn typed_cow() -> ::std::borrow::Cow<'static, str> { #typed_cow } },
+        quote! { pub fn float() -> ::ordered_float::OrderedFloat<f32> { #float } },
+        quote! { pub fn typed_float() -> ::ordered_float::OrderedFloat<f32> { #typed_float } },
+        quote! { pub fn map() -> ::std::collections::HashMap<u8, ::std::string::String> { #map } },
+        quote! { pub fn typed_map() -> ::std::collections::HashMap<u8, ::std::string::String> { #typed_map } },
+        quote! { pub fn modern_map() -> ::std::collections::HashMap<u8, ::std::string::String> { #modern_map } },
+        quote! { pub fn boxed() -> ::std::boxed::Box<::std::option::Option<i8>> { #boxed } },
+    ]);
+    check_fixture("no_implicit_prelude.rs", &source);
+
+    assert_eq!(prelude_free::tuple(), (None, vec![], BTreeMap::from_iter(vec![(1, None)])));
+    assert_eq!(prelude_free::cow(), "foo");
+    assert_eq!(prelude_free::typed_cow(), "foo");
+    assert_eq!(prelude_free::float(), OrderedFloat(1.5));
+    assert_eq!(prelude_free::typed_float(), OrderedFloat(1.5));
+    assert_eq!(prelude_free::map()[&1], "a");
+    assert_eq!(prelude_free::typed_map()[&1], "a");
+    assert_eq!(prelude_free::modern_map()[&1], "a");
+    assert_eq!(*prelude_free::boxed(), Some(-1));
+}
+
+#[no_implicit_prelude]
+#[allow(unused_parens, clippy::double_parens, clippy::type_complexity)]
+mod prelude_free {
+    ::std::include!("fixtures/no_implicit_prelude.rs");
+}
+
+#[test]
+fn test_target_supports() {
+    let options = Options::default();
+    assert_eq!(options.target_version(), None);
+    assert!(options.supports(1, 80));
+
+    let options = Options {
+        edition: Edition::Edition2018,
+        ..Options::default()
+    };
+    assert!(options.supports(1, 31));
+    assert!(!options.supports(1, 56));
+}
+
+#[test]
+fn test_target_edition_2018() {
+    let value = (MyBox(Box::new(1u8)), "foo".to_string(), BTreeMap::from_iter(vec![(1u8, 2u8)]));
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        edition: Edition::Edition2018,
+        ..Options::default()
+    };
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, options));
+
+    assert_eq!(
+        t.to_string(),
+        "( MyBox ( Box :: new ( 1u8 ) ) , \"foo\" . to_string ( ) , < ::std::collections::BTreeMap < _ , _ > as \
+         ::std::iter::FromIterator < _ > > :: from_iter ( vec! [ ( 1u8 , 2u8 , ) , ] ) , )"
+    );
+}
+
+#[test]
+fn test_target_edition_2021() {
+    let value = (MyBox(Box::new(1u8)), "foo".to_string(), BTreeMap::from_iter(vec![(1u8, 2u8)]));
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        edition: Edition::Edition2021,
+        ..Options::default()
+    };
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, options));
+
+    assert_eq!(
+        t.to_string(),
+        "( MyBox ( Box :: new ( 1u8 ) ) , String :: from ( \"foo\" ) , ::std::collections::BTreeMap :: from ( [ ( 1u8 , 2u8 , ) , ] ) , )"
+    );
+}
+
+#[test]
+fn test_target_rust_version() {
+    let value = (MyBoxedSlice(vec![1u8].into_boxed_slice()), HashMap::<u8, u8>::new());
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        rust_version: Some(RustVersion::new(1, 56)),
+        ..Options::default()
+    };
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, options));
+
+    assert_eq!(
+        t.to_string(),
+        "( MyBoxedSlice ( Box :: new ( ( * b\"\\x01\" ) ) ) , ::std::collections::HashMap :: from ( [ ] ) , )"
+    );
+}
+
+#[test]
+fn test_target_rust_version_below_edition() {
+    let value = "foo".to_string();
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        edition: Edition::Edition2021,
+        rust_version: Some(RustVersion::new(1, 31)),
+        ..Options::default()
+    };
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, options));
+
+    assert_eq!(t.to_string(), "String :: from ( \"foo\" )");
+}
+
+#[test]
+fn test_target_rust_version_old() {
+    let value = (MyBox(Box::new(1u8)), "foo".to_string());
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        rust_version: Some(RustVersion::new(1, 22)),
+        ..Options::default()
+    };
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, options));
+
+    assert_eq!(t.to_string(), "( MyBox ( Box :: new ( 1u8 ) ) , \"foo\" . to_string ( ) , )");
+}
+
+#[test]
+fn test_target_edition_2021_typed() {
+    let value = HashMap::<u8, String>::new();
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        typed: true,
+        edition: Edition::Edition2021,
+        ..Options::default()
+    };
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, options));
+
+    assert_eq!(t.to_string(), "::std::collections::HashMap :: < u8 , String > :: from ( [ ] )");
+}
+
+#[test]
+fn test_target_edition_2021_no_implicit_prelude() {
+    let value = (MyBox(Box::new(1u8)), "foo".to_string(), BTreeMap::from_iter(vec![(1u8, 2u8)]));
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        no_implicit_prelude: true,
+        edition: Edition::Edition2021,
+        ..Options::default()
+    };
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, options));
+
+    assert_eq!(
+        t.to_string(),
+        "( MyBox ( ::std::boxed::Box :: new ( 1u8 ) ) , < ::std::string::String as ::std::convert::From < & str > > :: from ( \"foo\" ) \
+         , < ::std::collections::BTreeMap < _ , _ > as ::std::convert::From < [ _ ; 1 ] > > :: from ( [ ( 1u8 , 2u8 , ) , ] ) , )"
+    );
+}
+
+#[test]
+fn test_target_edition_2021_output_compiles() {
+    let value = (Box::new(1u8), "foo".to_string(), BTreeMap::from_iter(vec![(1u8, 2u8)]));
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        edition: Edition::Edition2021,
+        ..Options::default()
+    };
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, options));
+
+    assert_eq!(
+        t.to_string(),
+        "( Box :: new ( 1u8 ) , String :: from ( \"foo\" ) , ::std::collections::BTreeMap :: from ( [ ( 1u8 , 2u8 , ) , ] ) , )"
+    );
+
+    let output = (Box::new(1u8), String::from("foo"), ::std::collections::BTreeMap::from([(1u8, 2u8)]));
+    assert_eq!(output, value);
+}
+
+#[test]
+fn test_constant() {
+    let value = MyConstStruct {
+        name: "foo",
+        bytes: &[1, 2],
+        cow: Cow::Borrowed("bar"),
+        list: vec![],
+        float: Some(OrderedFloat(1.5))
+    };
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        constant: true,
+        ..Options::default()
+    };
+
+    assert_eq!(value.try_to_custom_tokens(&mut t, options), Ok(()));
+    assert_eq!(
+        t.to_string(),
+        "MyConstStruct { name : \"foo\" , bytes : b\"\\x01\\x02\" , cow : ::std::borrow::Cow :: Borrowed ( \"bar\" ) , list : Vec :: new \
+         ( ) , float : Some ( ::ordered_float::OrderedFloat ( 1.5f32 ) ) }"
+    );
+
+    const OUTPUT: MyConstStruct<'static> = MyConstStruct {
+        name: "foo",
+        bytes: b"\x01\x02",
+        cow: ::std::borrow::Cow::Borrowed("bar"),
+        list: Vec::new(),
+        float: Some(::ordered_float::OrderedFloat(1.5f32))
+    };
+    assert_eq!(OUTPUT, value);
+}
+
+#[test]
+fn test_constant_empty_collections() {
+    let value: (String, Vec<u8>, BTreeMap<u8, u8>, [u8; 0]) = (String::new(), vec![], BTreeMap::new(), []);
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        constant: true,
+        typed: true,
+        ..Options::default()
+    };
+
+    assert_eq!(value.try_to_custom_tokens(&mut t, options), Ok(()));
+    assert_eq!(
+        t.to_string(),
+        "( String :: new ( ) , Vec :: < u8 > :: new ( ) , ::std::collections::BTreeMap :: < u8 , u8 > :: new ( ) , { const EMPTY : [ u8 ; \
+         0 ] = [ ] ; EMPTY } , )"
+    );
+}
+
+#[test]
+fn test_constant_not_const_field() {
+    let value = MyExampleStruct {
+        foo: "Hello".to_string(),
+        bar: MyExampleTupleStruct(String::new()),
+        baz: vec![]
+    };
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        constant: true,
+        ..Options::default()
+    };
+    let error = value.try_to_custom_tokens(&mut t, options).unwrap_err();
+
+    assert_eq!(error.path(), ".foo");
+    assert_eq!(
+        error.kind(),
+        &ErrorKind::NotConst {
+            type_name: std::any::type_name::<String>()
+        }
+    );
+}
+
+#[test]
+fn test_constant_not_const_nested_field() {
+    let value = (1u8, [MyBox(Box::new(1u8))]);
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        constant: true,
+        edition: Edition::Edition2021,
+        ..Options::default()
+    };
+    let error = value.try_to_custom_tokens(&mut t, options).unwrap_err();
+
+    assert_eq!(error.path(), ".1[0].0");
+    assert_eq!(
+        error.to_string(),
+        format!("field `.1[0].0` of type `{}` cannot be constructed in a const context", std::any::type_name::<Box<u8>>())
+    );
+}
+
+#[test]
+fn test_constant_rust_version() {
+    let value: BTreeMap<u8, u8> = BTreeMap::new();
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        constant: true,
+        rust_version: Some(RustVersion::new(1, 56)),
+        ..Options::default()
+    };
+    let error = value.try_to_custom_tokens(&mut t, options).unwrap_err();
+
+    assert_eq!(error.path(), "");
+    assert_eq!(
+        error.to_string(),
+        format!("value of type `{}` cannot be constructed in a const context", std::any::type_name::<BTreeMap<u8, u8>>())
+    );
+}
+
+#[test]
+fn test_mirror() {
+    let value = MyMirrorStruct {
+        name: "foo".to_string(),
+        values: vec![
+            MyMirrorEnum::Unit,
+            MyMirrorEnum::Tuple(-1, "bar".to_string()),
+            MyMirrorEnum::Named { flag: true },
+        ],
+        lookup: BTreeMap::from_iter(vec![("baz".to_string(), Some(1)), ("qux".to_string(), None)]),
+        boxed: Box::new(MyMirrorTupleStruct(Cow::Owned("quux".to_string()), (2, 'x')))
+    };
+    let mut t = quote::Tokens::new();
+    value.to_static_tokens(&mut Context::new(&mut t));
+
+    assert_eq!(
+        t.to_string(),
+        "MyMirrorStructStatic { name : \"foo\" , values : & [ MyMirrorEnumStatic :: Unit , MyMirrorEnumStatic :: Tuple ( ( -1i32 ) , \
+         \"bar\" , ) , MyMirrorEnumStatic :: Named { flag : true , } , ] , lookup : & [ ( \"baz\" , Some ( 1u32 ) ) , ( \"qux\" , None ) , \
+         ] , boxed : & { MyMirrorTupleStructStatic ( \"quux\" , ( 2u8 , 'x' , ) , ) } , }"
+    );
+
+    static MIRROR: MyMirrorStructStatic = MyMirrorStructStatic {
+        name: "foo",
+        values: &[
+            MyMirrorEnumStatic::Unit,
+            MyMirrorEnumStatic::Tuple(-1i32, "bar"),
+            MyMirrorEnumStatic::Named { flag: true },
+        ],
+        lookup: &[("baz", Some(1u32)), ("qux", None)],
+        boxed: &MyMirrorTupleStructStatic("quux", (2u8, 'x'))
+    };
+    assert_eq!(MyMirrorStruct::from(&MIRROR), value);
+}
+
+#[test]
+fn test_mirror_bytes_output_compiles() {
+    let value = MyMirrorBytes {
+        boxed: Box::new(vec![1, 2]),
+        shared: Arc::new(vec![3]),
+        counted: Rc::new(Some(vec![]))
+    };
+    let mut t = quote::Tokens::new();
+    value.to_static_tokens(&mut Context::new(&mut t));
+
+    assert_eq!(
+        t.to_string(),
+        "MyMirrorBytesStatic { boxed : & { b\"\\x01\\x02\" } , shared : & { b\"\\x03\" } , counted : & { Some ( & [ ] ) } , }"
+    );
+    check_fixture(
+        "mirror_bytes.rs",
+        &fixture_source(vec![quote! { use super::MyMirrorBytesStatic; }, quote! { pub static BYTES: MyMirrorBytesStatic = #t; }])
+    );
+    assert_eq!(MyMirrorBytes::from(&mirror_bytes::BYTES), value);
+}
+
+mod mirror_bytes {
+    include!("fixtures/mirror_bytes.rs");
+}
+
+#[test]
+fn test_mirror_unit_variant() {
+    let value = MyMirrorEnum::Unit;
+    let mut t = quote::Tokens::new();
+    value.to_static_tokens(&mut Context::new(&mut t));
+
+    assert_eq!(t.to_string(), "MyMirrorEnumStatic :: Unit");
+    assert_eq!(MyMirrorEnum::from(&MyMirrorEnumStatic::Unit), value);
+}
+
+#[test]
+fn test_lazy_static() {
+    let value = HashMap::from_iter(vec![(1u8, MyStructC { foo: "foo".to_string() })]);
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        edition: Edition::Edition2021,
+        rust_version: Some(RustVersion::new(1, 80)),
+        ..Options::default()
+    };
+    append_lazy_static(&mut Context::with_options(&mut t, options), "pub", "LOOKUP", &value);
+
+    assert_eq!(
+        t.to_string(),
+        "pub static LOOKUP : ::std::sync::LazyLock < ::std::collections::HashMap < u8 , MyStructC > > = ::std::sync::LazyLock :: new ( || \
+         ::std::collections::HashMap :: from ( [ ( 1u8 , MyStructC { foo : String :: from ( \"foo\" ) } , ) , ] ) ) ;"
+    );
+
+    static LOOKUP: ::std::sync::LazyLock<::std::collections::HashMap<u8, MyStructC>> =
+        ::std::sync::LazyLock::new(|| ::std::collections::HashMap::from([(1u8, MyStructC { foo: String::from("foo") })]));
+    assert_eq!(LOOKUP[&1].foo, value[&1].foo);
+}
+
+#[test]
+fn test_lazy_static_default() {
+    let value = vec![1u8, 2];
+    let mut t = quote::Tokens::new();
+    append_lazy_static(&mut Context::new(&mut t), "", "BYTES", &value);
+
+    assert_eq!(
+        t.to_string(),
+        "static BYTES : ::std::sync::LazyLock < Vec < u8 > > = ::std::sync::LazyLock :: new ( || b\"\\x01\\x02\" . to_vec ( ) ) ;"
+    );
+}
+
+#[test]
+fn test_lazy_static_once_cell() {
+    let value = vec![1u8, 2];
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        rust_version: Some(RustVersion::new(1, 70)),
+        ..Options::default()
+    };
+    append_lazy_static(&mut Context::with_options(&mut t, options), "", "BYTES", &value);
+
+    assert_eq!(
+        t.to_string(),
+        "static BYTES : ::once_cell::sync::Lazy < Vec < u8 > > = ::once_cell::sync::Lazy :: new ( || b\"\\x01\\x02\" . to_vec ( ) ) ;"
+    );
+}
+
+#[test]
+fn test_lazy_static_not_thread_safe() {
+    let value = MyRc(Rc::new(1));
+    let mut t = quote::Tokens::new();
+    let mut ctx = Context::new(&mut t);
+    append_lazy_static(&mut ctx, "", "SHARED", &value);
+
+    let error = ctx.finish().unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::NotThreadSafe { type_name: std::any::type_name::<MyRc>() });
+}
+
+#[test]
+fn test_lazy_static_unnamed_type() {
+    let value = vec![MyHandWritten(1)];
+    let mut t = quote::Tokens::new();
+    let mut ctx = Context::new(&mut t);
+    append_lazy_static(&mut ctx, "", "LEGACY", &value);
+
+    let error = ctx.finish().unwrap_err();
+    assert_eq!(error.path(), "");
+    assert_eq!(error.kind(), &ErrorKind::UnnamedType { type_name: std::any::type_name::<Vec<MyHandWritten>>() });
+    assert_eq!(
+        error.to_string(),
+        format!("value of type `{}` has no name to emit it with", std::any::type_name::<Vec<MyHandWritten>>())
+    );
+}
+
+#[test]
+fn test_thread_local() {
+    let value = MyRc(Rc::new(1));
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        edition: Edition::Edition2018,
+        ..Options::default()
+    };
+    let mut ctx = Context::with_options(&mut t, options);
+    append_thread_local(&mut ctx, "pub(crate)", "SHARED", &value);
+
+    assert_eq!(ctx.finish(), Ok(()));
+    assert_eq!(
+        t.to_string(),
+        "thread_local! { pub(crate) static SHARED : MyRc = MyRc ( ::std::rc::Rc :: new ( 1u8 ) ) ; }"
+    );
+}
+
+#[test]
+fn test_runs_array() {
+    let value = ([0i32; 16], [MyTupleStructA(1); 0], [true; 8]);
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(t.to_string(), "( [ 0i32 ; 16 ] , [ ] , [ true ; 8 ] , )");
+}
+
+#[test]
+fn test_runs_vec_clone() {
+    let value = vec![Some("foo".to_string()); 8];
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(t.to_string(), "vec! [ Some ( \"foo\" . to_string ( ) ) ; 8 ]");
+}
+
+#[test]
+fn test_runs_vec_chain() {
+    let mut value = vec![1i32, 2];
+    value.extend(vec![0; 10]);
+    value.push(3);
+    value.extend(vec![7; 8]);
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(
+        t.to_string(),
+        "::std::iter::IntoIterator::into_iter ( vec! [1, 2] ) . chain ( ::std::iter::repeat ( 0i32 ) . take ( 10 ) ) . chain ( vec! [3] ) \
+         . chain ( ::std::iter::repeat ( 7i32 ) . take ( 8 ) ) . collect :: < Vec < _ > > ( )"
+    );
+
+    let output = ::std::iter::IntoIterator::into_iter(vec![1, 2])
+        .chain(::std::iter::repeat(0i32).take(10))
+        .chain(vec![3])
+        .chain(::std::iter::repeat(7i32).take(8))
+        .collect::<Vec<_>>();
+    assert_eq!(output, value);
+}
+
+#[test]
+fn test_runs_tag_only_eq() {
+    let value: Vec<MyTagged> = (0..8).map(|id| MyTagged { tag: 0, id }).collect();
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(
+        t.to_string(),
+        "vec! [ MyTagged { tag : 0u8 , id : 0u32 } , MyTagged { tag : 0u8 , id : 1u32 } , MyTagged { tag : 0u8 , id : 2u32 } , \
+         MyTagged { tag : 0u8 , id : 3u32 } , MyTagged { tag : 0u8 , id : 4u32 } , MyTagged { tag : 0u8 , id : 5u32 } , \
+         MyTagged { tag : 0u8 , id : 6u32 } , MyTagged { tag : 0u8 , id : 7u32 } , ]"
+    );
+}
+
+#[test]
+fn test_runs_signed_zero() {
+    let value = [0.0f64, -0.0, 0.0, -0.0, 0.0, -0.0, 0.0, -0.0];
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(t.to_string(), "[ 0f64 , ( -0f64 ) , 0f64 , ( -0f64 ) , 0f64 , ( -0f64 ) , 0f64 , ( -0f64 ) , ]");
+}
+
+#[test]
+fn test_runs_separate_rcs() {
+    let value: Vec<Rc<u8>> = (0..9).map(|_| Rc::new(1)).collect();
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    // One `Rc::new` each, not `vec![Rc::new(1u8); 9]`, which would share a single one.
+    assert_eq!(t.to_string().matches("::std::rc::Rc :: new ( 1u8 ) ,").count(), 9);
+}
+
+#[test]
+fn test_runs_vec_no_implicit_prelude() {
+    let mut value = vec![0i32; 8];
+    value.push(1);
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        no_implicit_prelude: true,
+        ..Options::default()
+    };
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, options));
+
+    assert_eq!(t.to_string(), "::std::vec! [0, 0, 0, 0, 0, 0, 0, 0, 1]");
+}
+
+#[test]
+fn test_compact_vec_u16() {
+    let value: Vec<u16> = (0..16).map(|i| 0x6161 + i).collect();
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        compact_numbers: true,
+        ..Options::default()
+    };
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, options));
+
+    assert_eq!(
+        t.to_string(),
+        "{ const BYTES : & [ u8 ] = b\"aabacadaeafagahaiajakalamanaoapa\" ; fn decode ( ) -> Vec < u16 > { let mut items = Vec :: \
+         with_capacity ( 16 ) ; let mut i = 0 ; while i < 16 { let at = i * 2 ; items . push ( u16 :: from_le_bytes ( [ BYTES [ at ] , \
+         BYTES [ at + 1 ] , ] ) ) ; i += 1 ; } items } decode ( ) }"
+    );
+
+    let output = {
+        const BYTES: &[u8] = b"aabacadaeafagahaiajakalamanaoapa";
+        fn decode() -> Vec<u16> {
+            let mut items = Vec::with_capacity(16);
+            let mut i = 0;
+            while i < 16 {
+                let at = i * 2;
+                items.push(u16::from_le_bytes([BYTES[at], BYTES[at + 1]]));
+                i += 1;
+            }
+            items
+        }
+        decode()
+    };
+    assert_eq!(output, value);
+}
+
+#[test]
+fn test_compact_not_constant() {
+    let value: Vec<u16> = (0..16).collect();
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        compact_numbers: true,
+        constant: true,
+        ..Options::default()
+    };
+    value[..].to_custom_tokens_with(&mut Context::with_options(&mut t, options));
+
+    // Evaluating a `const fn` decode would take rustc longer than the literal.
+    assert_eq!(t.to_string(), "[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]");
+}
+
+#[test]
+fn test_compact_runtime_decode() {
+    let value: Vec<i16> = (0..16).map(|i| i - 0x9f).collect();
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        compact_numbers: true,
+        rust_version: Some(RustVersion::new(1, 40)),
+        ..Options::default()
+    };
+    value[..].to_custom_tokens_with(&mut Context::with_options(&mut t, options));
+
+    assert_eq!(
+        t.to_string(),
+        "{ const BYTES : & [ u8 ] = b\"a\\xffb\\xffc\\xffd\\xffe\\xfff\\xffg\\xffh\\xffi\\xffj\\xffk\\xffl\\xffm\\xffn\\xffo\\xffp\\xff\" \
+         ; fn decode ( ) -> [ i16 ; 16 ] { let mut items = [ 0i16 ; 16 ] ; let mut i = 0 ; while i < 16 { let at = i * 2 ; items [ i ] = \
+         i16 :: from_le_bytes ( [ BYTES [ at ] , BYTES [ at + 1 ] , ] ) ; i += 1 ; } items } decode ( ) }"
+    );
+}
+
+#[test]
+fn test_compact_fallback() {
+    let value = (vec![1u32; 15], (0..16).map(|i| i as f32).collect::<Vec<_>>(), (0..16).collect::<Vec<usize>>());
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        compact_numbers: true,
+        constant: true,
+        rust_version: Some(RustVersion::new(1, 60)),
+        ..Options::default()
+    };
+    (&value.0[..], &value.1[..], &value.2[..]).to_custom_tokens_with(&mut Context::with_options(&mut t, options));
+
+    assert_eq!(
+        t.to_string(),
+        "( & [ 1u32 ; 15 ] , & [ 0f32 , 1f32 , 2f32 , 3f32 , 4f32 , 5f32 , 6f32 , 7f32 , 8f32 , 9f32 , 10f32 , 11f32 , 12f32 , 13f32 , \
+         14f32 , 15f32 , ] , & [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15] , )"
+    );
+}
+
+#[test]
+fn test_compressed_round_trip_ttf() {
+    let font = include_bytes!("fixtures/FreeSans.ttf").to_vec();
+    let value = Compressed::new(font.clone());
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+
+    assert!(t.as_str().starts_with("::self_tokenize_trait::Compressed :: from_compressed ( b\""));
+    let compressed = parse_compressed_argument(&t);
+    assert!(compressed.len() < font.len() * 2 / 3);
+
+    let output = Compressed::<Vec<u8>>::from_compressed(Box::leak(compressed.into_boxed_slice()));
+    assert_eq!(*output, font);
+}
+
+#[test]
+fn test_compressed_static_string() {
+    let text = "Hello, world! ".repeat(100);
+    let value = Compressed::new(text.clone());
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, Options { typed: true, constant: true, ..Options::default() }));
+
+    assert!(t.as_str().starts_with("::self_tokenize_trait::Compressed :: < String > :: from_compressed ( b\""));
+    let compressed: &'static [u8] = Box::leak(parse_compressed_argument(&t).into_boxed_slice());
+    assert!(compressed.len() < 100);
+
+    let output = Compressed::<String>::from_compressed(compressed);
+    assert_eq!(*output, text);
+
+    // Values created from a blob are emitted from it, whether or not they were decompressed.
+    let mut t2 = quote::Tokens::new();
+    output.to_custom_tokens(&mut t2);
+    assert_eq!(parse_compressed_argument(&t2), compressed);
+
+    let mut t3 = quote::Tokens::new();
+    Compressed::<String>::from_compressed(compressed).to_custom_tokens(&mut t3);
+    assert_eq!(parse_compressed_argument(&t3), compressed);
+}
+
+#[test]
+fn test_compressed_static_item() {
+    static LOCALE: Compressed<String> = Compressed::from_compressed(b"\xf3H\xcd\xc9\xc9\x07\x00");
+
+    assert_eq!(LOCALE.as_str(), "Hello");
+}
+
+fn parse_compressed_argument(tokens: &quote::Tokens) -> Vec<u8> {
+    match syn::parse_expr(tokens.as_str()).unwrap().node {
+        syn::ExprKind::Call(_, ref args) => decode_bytes_expr(&args[0]),
+        other => panic!("Expected `from_compressed` call, found `{:?}`", other)
+    }
+}
+
+#[test]
+fn test_chunked_vec() {
+    let value = vec![1i32, 2, 3, 4, 5];
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        chunk_size: Some(2),
+        ..Options::default()
+    };
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, options));
+
+    assert_eq!(
+        t.to_string(),
+        "{ fn part_0 ( ) -> Vec < i32 > { vec! [1, 2] } fn part_1 ( ) -> Vec < i32 > { vec! [3, 4] } fn part_2 ( ) -> Vec < i32 > { vec! \
+         [5] } let mut items = Vec :: with_capacity ( 5 ) ; items . append ( & mut part_0 ( ) ) ; items . append ( & mut part_1 ( ) ) ; \
+         items . append ( & mut part_2 ( ) ) ; items }"
+    );
+
+    let output = {
+        fn part_0() -> Vec<i32> {
+            vec![1, 2]
+        }
+        fn part_1() -> Vec<i32> {
+            vec![3, 4]
+        }
+        fn part_2() -> Vec<i32> {
+            vec![5]
+        }
+        let mut items = Vec::with_capacity(5);
+        items.append(&mut part_0());
+        items.append(&mut part_1());
+        items.append(&mut part_2());
+        items
+    };
+    assert_eq!(output, value);
+}
+
+#[test]
+fn test_chunked_vec_unnamed_type() {
+    let value = (1u8, vec![MyHandWritten(1), MyHandWritten(2), MyHandWritten(3)]);
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        chunk_size: Some(2),
+        ..Options::default()
+    };
+    let error = value.try_to_custom_tokens(&mut t, options).unwrap_err();
+
+    assert_eq!(error.path(), ".1");
+    assert_eq!(error.kind(), &ErrorKind::UnnamedType { type_name: std::any::type_name::<Vec<MyHandWritten>>() });
+}
+
+#[test]
+fn test_chunked_module() {
+    let value = (vec![MyTupleStructA(1), MyTupleStructA(2), MyTupleStructA(3)], vec![vec![0u8; 3]; 2]);
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        chunk_size: Some(2),
+        ..Options::default()
+    };
+    append_chunked_module(&mut Context::with_options(&mut t, options), "pub", "table", &value);
+
+    assert_eq!(
+        t.to_string(),
+        "pub mod table { use super :: * ; pub ( super ) fn value ( ) -> ( Vec < MyTupleStructA > , Vec < Vec < u8 > > , ) { ( { let mut \
+         items = Vec :: with_capacity ( 3 ) ; items . append ( & mut part_0 ( ) ) ; items . append ( & mut part_1 ( ) ) ; items } , vec! \
+         [ { let mut items = Vec :: with_capacity ( 3 ) ; items . append ( & mut part_2 ( ) ) ; items . append ( & mut part_3 ( ) ) ; \
+         items } , { let mut items = Vec :: with_capacity ( 3 ) ; items . append ( & mut part_4 ( ) ) ; items . append ( & mut part_5 ( ) \
+         ) ; items } , ] , ) } fn part_0 ( ) -> Vec < MyTupleStructA > { vec! [ MyTupleStructA ( 1i32 ) , MyTupleStructA ( 2i32 ) , ] } \
+         fn part_1 ( ) -> Vec < MyTupleStructA > { vec! [ MyTupleStructA ( 3i32 ) , ] } fn part_2 ( ) -> Vec < u8 > { b\"\\x00\\x00\" . \
+         to_vec ( ) } fn part_3 ( ) -> Vec < u8 > { b\"\\x00\" . to_vec ( ) } fn part_4 ( ) -> Vec < u8 > { b\"\\x00\\x00\" . to_vec ( ) \
+         } fn part_5 ( ) -> Vec < u8 > { b\"\\x00\" . to_vec ( ) } }"
+    );
+
+    pub mod table {
+        use super::*;
+        pub(super) fn value() -> (Vec<MyTupleStructA>, Vec<Vec<u8>>) {
+            (
+                {
+                    let mut items = Vec::with_capacity(3);
+                    items.append(&mut part_0());
+                    items.append(&mut part_1());
+                    items
+                },
+                vec![
+                    {
+                        let mut items = Vec::with_capacity(3);
+                        items.append(&mut part_2());
+                        items.append(&mut part_3());
+                        items
+                    },
+                    {
+                        let mut items = Vec::with_capacity(3);
+                        items.append(&mut part_4());
+                        items.append(&mut part_5());
+                        items
+                    },
+                ]
+            )
+        }
+        fn part_0() -> Vec<MyTupleStructA> {
+            vec![MyTupleStructA(1i32), MyTupleStructA(2i32)]
+        }
+        fn part_1() -> Vec<MyTupleStructA> {
+            vec![MyTupleStructA(3i32)]
+        }
+        fn part_2() -> Vec<u8> {
+            b"\x00\x00".to_vec()
+        }
+        fn part_3() -> Vec<u8> {
+            b"\x00".to_vec()
+        }
+        fn part_4() -> Vec<u8> {
+            b"\x00\x00".to_vec()
+        }
+        fn part_5() -> Vec<u8> {
+            b"\x00".to_vec()
+        }
+    }
+    let output = table::value();
+    assert!(output.0 == value.0);
+    assert_eq!(output.1, value.1);
+}
+
+#[test]
+fn test_heap_direct_vec() {
+    let value = vec![
+        MyHeapStruct {
+            id: 1,
+            samples: [1, 2, 3, 4]
+        },
+        MyHeapStruct {
+            id: 2,
+            samples: [0; 4]
+        },
+    ];
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, Options { typed: true, ..Options::default() }));
+
+    assert_eq!(
+        t.to_string(),
+        "{ let mut items = Vec :: < MyHeapStruct > :: with_capacity ( 2 ) ; items . push ( MyHeapStruct { id : 1u32 , samples : [1u16, \
+         2u16, 3u16, 4u16] } ) ; items . push ( MyHeapStruct { id : 2u32 , samples : [0u16, 0u16, 0u16, 0u16] } ) ; items }"
+    );
+
+    let output = {
+        let mut items = Vec::<MyHeapStruct>::with_capacity(2);
+        items.push(MyHeapStruct {
+            id: 1u32,
+            samples: [1u16, 2u16, 3u16, 4u16]
+        });
+        items.push(MyHeapStruct {
+            id: 2u32,
+            samples: [0u16, 0u16, 0u16, 0u16]
+        });
+        items
+    };
+    assert_eq!(output, value);
+}
+
+#[test]
+fn test_dedup() {
+    let red = MyStyle {
+        color: "red".to_string(),
+        border: Some("1px solid black".to_string())
+    };
+    let blue = MyStyle {
+        color: "blue".to_string(),
+        border: Some("1px solid black".to_string())
+    };
+    let value = vec![red.clone(), blue.clone(), red.clone(), red.clone(), blue.clone(), red.clone(), blue.clone(), blue.clone()];
+    let mut t = quote::Tokens::new();
+    let report = append_deduplicated(&mut Context::new(&mut t), &value);
+
+    assert_eq!(
+        t.to_string(),
+        "{ let dedup_0 : MyStyle = MyStyle { color : \"red\" . to_string ( ) , border : Some ( \"1px solid black\" . to_string ( \
+         ) ) } ; let dedup_1 : MyStyle = MyStyle { color : \"blue\" . to_string ( ) , border : Some ( \"1px solid black\" . \
+         to_string ( ) ) } ; vec! [ ::std::clone::Clone :: clone ( & dedup_0 ) , ::std::clone::Clone :: clone ( & dedup_1 ) , \
+         ::std::clone::Clone :: clone ( & dedup_0 ) , ::std::clone::Clone :: clone ( & dedup_0 ) , ::std::clone::Clone :: clone ( \
+         & dedup_1 ) , ::std::clone::Clone :: clone ( & dedup_0 ) , ::std::clone::Clone :: clone ( & dedup_1 ) , \
+         ::std::clone::Clone :: clone ( & dedup_1 ) , ] }"
+    );
+    assert_eq!(report.to_string(), "2 subvalues hoisted, 796 -> 617 bytes (22.5% smaller)");
+
+    let output = {
+        let dedup_0: MyStyle = MyStyle {
+            color: "red".to_string(),
+            border: Some("1px solid black".to_string())
+        };
+        let dedup_1: MyStyle = MyStyle {
+            color: "blue".to_string(),
+            border: Some("1px solid black".to_string())
+        };
+        let red = || ::std::clone::Clone::clone(&dedup_0);
+        let blue = || ::std::clone::Clone::clone(&dedup_1);
+        vec![red(), blue(), red(), red(), blue(), red(), blue(), blue()]
+    };
+    assert_eq!(output, value);
+}
+
+#[test]
+fn test_dedup_constant() {
+    let value = [
+        ("a long string that repeats itself", 1u8),
+        ("a long string that repeats itself", 2u8),
+        ("a long string that repeats itself", 3u8),
+    ];
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        constant: true,
+        ..Options::default()
+    };
+    let report = append_deduplicated(&mut Context::with_options(&mut t, options), &value);
+
+    assert_eq!(
+        t.to_string(),
+        "{ const DEDUP_0 : & str = \"a long string that repeats itself\" ; [ ( DEDUP_0 , 1u8 , ) , ( DEDUP_0 , 2u8 , ) , ( DEDUP_0 , \
+         3u8 , ) , ] }"
+    );
+    assert_eq!(report.bindings, 1);
+
+    const OUTPUT: [(&str, u8); 3] = {
+        const DEDUP_0: &str = "a long string that repeats itself";
+        [(DEDUP_0, 1u8), (DEDUP_0, 2u8), (DEDUP_0, 3u8)]
+    };
+    assert_eq!(OUTPUT, value);
+}
+
+#[test]
+fn test_dedup_shared() {
+    let text = "a long string that repeats itself, in four separately allocated reference counted boxes";
+    let value: Vec<Rc<String>> = (0..4).map(|_| Rc::new(text.to_string())).collect();
+    let mut t = quote::Tokens::new();
+    let report = append_deduplicated(&mut Context::new(&mut t), &value);
+
+    // Only the strings are hoisted: a hoisted `Rc` would be shared by every
+    // element instead of allocated once for each.
+    assert_eq!(
+        t.to_string(),
+        "{ let dedup_0 : String = \"a long string that repeats itself, in four separately allocated reference counted boxes\" . \
+         to_string ( ) ; vec! [ ::std::rc::Rc :: new ( ::std::clone::Clone :: clone ( & dedup_0 ) ) , ::std::rc::Rc :: new ( \
+         ::std::clone::Clone :: clone ( & dedup_0 ) ) , ::std::rc::Rc :: new ( ::std::clone::Clone :: clone ( & dedup_0 ) ) , \
+         ::std::rc::Rc :: new ( ::std::clone::Clone :: clone ( & dedup_0 ) ) , ] }"
+    );
+    assert_eq!(report.bindings, 1);
+
+    let output = {
+        let dedup_0: String = "a long string that repeats itself, in four separately allocated reference counted boxes".to_string();
+        let shared = || ::std::rc::Rc::new(::std::clone::Clone::clone(&dedup_0));
+        vec![shared(), shared(), shared(), shared()]
+    };
+    assert_eq!(output, value);
+    let mut again = quote::Tokens::new();
+    append_deduplicated(&mut Context::new(&mut again), &output);
+    assert_eq!(again.to_string(), t.to_string());
+}
+
+#[test]
+fn test_dedup_chunk_size() {
+    let text = "a long string that repeats itself in every chunk, often enough for a binding to pay off";
+    let value = vec![text.to_string(); 4];
+    let mut t = quote::Tokens::new();
+    let options = Options {
+        chunk_size: Some(2),
+        ..Options::default()
+    };
+    append_deduplicated(&mut Context::with_options(&mut t, options), &value);
+
+    // `fn part_N` items could not refer to `dedup_0`, so nothing is chunked.
+    assert_eq!(
+        t.to_string(),
+        "{ let dedup_0 : String = \"a long string that repeats itself in every chunk, often enough for a binding to pay off\" . \
+         to_string ( ) ; vec! [ ::std::clone::Clone :: clone ( & dedup_0 ) , ::std::clone::Clone :: clone ( & dedup_0 ) , \
+         ::std::clone::Clone :: clone ( & dedup_0 ) , ::std::clone::Clone :: clone ( & dedup_0 ) , ] }"
+    );
+
+    let output = {
+        let dedup_0: String = "a long string that repeats itself in every chunk, often enough for a binding to pay off".to_string();
+        let text = || ::std::clone::Clone::clone(&dedup_0);
+        vec![text(), text(), text(), text()]
+    };
+    assert_eq!(output, value);
+}
+
+#[test]
+fn test_sink_string() {
+    let value = vec![Some((1u8, "one")), None];
+    let mut t = quote::Tokens::new();
+    value.to_custom_tokens(&mut t);
+    let mut s = String::new();
+    value.to_custom_tokens(&mut s);
+
+    assert_eq!(s, "vec! [ Some ( ( 1u8 , \"one\" , ) ) , None , ]");
+    assert_eq!(s, t.to_string());
+}
+
+#[test]
+fn test_sink_write() {
+    let value = MyStyle {
+        color: "blue".to_string(),
+        border: Some("dotted".to_string())
+    };
+    let mut sink = WriteSink::new(vec![]);
+    value.to_custom_tokens(&mut sink);
+    let bytes = sink.finish().unwrap();
+
+    assert_eq!(
+        String::from_utf8(bytes).unwrap(),
+        "MyStyle { color : \"blue\" . to_string ( ) , border : Some ( \"dotted\" . to_string ( ) ) }"
+    );
+}
+
+#[test]
+fn test_sink_write_error() {
+    struct Full;
+
+    impl io::Write for Full {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut sink = WriteSink::new(Full);
+    vec![1u8, 2, 3].to_custom_tokens(&mut sink);
+
+    assert_eq!(sink.finish().err().unwrap().to_string(), "disk full");
+}
+
+#[test]
+fn test_sink_write_dedup() {
+    let value = ["a long string that repeats itself", "a long string that repeats itself"];
+    let mut t = quote::Tokens::new();
+    append_deduplicated(&mut Context::new(&mut t), &value);
+    let mut sink = WriteSink::new(vec![]);
+    let report = append_deduplicated(&mut Context::new(&mut sink), &value);
+    let bytes = sink.finish().unwrap();
+
+    assert_eq!(String::from_utf8(bytes).unwrap(), t.to_string());
+    assert_eq!(report.deduplicated_size, t.as_str().len());
+}
+
+#[test]
+fn test_sink_write_parts() {
+    struct Recorder {
+        bytes: Vec<u8>,
+        largest_write: usize
+    }
+
+    impl io::Write for Recorder {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.largest_write = self.largest_write.max(buf.len());
+            self.bytes.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let bytes: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+    let numbers: Vec<u32> = (0..20_000).collect();
+    let mut t = quote::Tokens::new();
+    bytes.to_custom_tokens(&mut t);
+    numbers[..].to_custom_tokens(&mut t);
+    let mut sink = WriteSink::new(Recorder {
+        bytes: vec![],
+        largest_write: 0
+    });
+    bytes.to_custom_tokens(&mut sink);
+    numbers[..].to_custom_tokens(&mut sink);
+    let recorder = sink.finish().unwrap();
+
+    assert_eq!(String::from_utf8(recorder.bytes).unwrap(), t.to_string());
+    assert!(recorder.largest_write < 300_000);
+}
+
+#[test]
+fn test_sink_parts_budget() {
+    let numbers: Vec<u32> = (0..20_000).collect();
+    let options = Options {
+        budget: Budget {
+            max_bytes: Some(1000),
+            ..Budget::default()
+        },
+        ..Options::default()
+    };
+    let mut s = String::new();
+    let result = numbers[..].try_to_custom_tokens(&mut s, options);
+
+    assert_eq!(result.unwrap_err().to_string(), "value exceeds the budget of 1000 bytes");
+    assert!(s.len() <= 1000);
+}
+
+#[test]
+fn test_sink_token_stream() {
+    let value = MyStyle {
+        color: "red".to_string(),
+        border: None
+    };
+    let mut sink = TokenStreamSink::new();
+    value.to_custom_tokens(&mut sink);
+    let stream = sink.into_token_stream().unwrap();
+
+    assert_eq!(stream.to_string(), "MyStyle { color : \"red\" . to_string () , border : None }");
+}
+
+fn tokenize_parallel<T>(value: &T, options: Options) -> (String, String, Result<(), self_tokenize_trait::Error>)
+where
+    T: ?Sized + ToCustomTokens + Sync
+{
+    let mut sequential = String::new();
+    let sequential_result = value.try_to_custom_tokens(&mut sequential, options.clone());
+    // Single threaded pools are skipped, so bring threads of our own on one core.
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    let mut parallel = String::new();
+    let parallel_result = pool.install(|| {
+        value.try_to_custom_tokens(
+            &mut parallel,
+            Options {
+                parallel: true,
+                ..options
+            }
+        )
+    });
+    assert_eq!(sequential_result, parallel_result);
+    (sequential, parallel, parallel_result)
+}
+
+#[test]
+fn test_parallel_vec() {
+    let value: Vec<MyStyle> = (0..5000)
+        .map(|i| MyStyle {
+            color: format!("#{:06x}", i),
+            border: if i % 3 == 0 { None } else { Some(format!("{}px", i)) }
+        })
+        .collect();
+    let (sequential, parallel, _) = tokenize_parallel(&value, Options::default());
+
+    assert!(sequential == parallel);
+    assert!(parallel.starts_with("vec! [ MyStyle { color : \"#000000\" . to_string ( ) , border : None } , MyStyle {"));
+}
+
+#[test]
+fn test_parallel_heap_vec_and_map() {
+    let value: Vec<MyHeapStruct> = (0..3000)
+        .map(|i| MyHeapStruct {
+            id: i,
+            samples: [i as u16, 1, 2, 3]
+        })
+        .collect();
+    let (sequential, parallel, _) = tokenize_parallel(&value, Options::default());
+    assert!(sequential == parallel);
+
+    let value: BTreeMap<u32, Vec<u8>> = (0..3000).map(|i| (i, vec![i as u8; i as usize % 16])).collect();
+    let (sequential, parallel, _) = tokenize_parallel(&value, Options::default());
+    assert!(sequential == parallel);
+}
+
+#[test]
+fn test_parallel_error() {
+    let value: Vec<Option<Box<u8>>> = (0..4000)
+        .map(|i| if i == 2500 || i == 3500 { Some(Box::new(1)) } else { None })
+        .collect();
+    let options = Options {
+        constant: true,
+        ..Options::default()
+    };
+    let (sequential, parallel, result) = tokenize_parallel(&value[..], options);
+
+    assert!(sequential == parallel);
+    assert_eq!(result.unwrap_err().path(), "[2500]");
+}
+
+#[test]
+fn test_deep_chain() {
+    const DEPTH: usize = 1_000_000;
+    let mut chain = None;
+    for value in 0..DEPTH as u32 {
+        chain = Some(Box::new(MyNode { value, next: chain }));
+    }
+    let mut t = String::new();
+    chain.to_custom_tokens(&mut t);
+
+    // Dropping the chain recursively would overflow the stack as well, so
+    // take it apart before any assertion can unwind.
+    while let Some(node) = chain {
+        chain = node.next;
+    }
+
+    assert!(t.starts_with("Some ( ( box MyNode { value : 999999u32 , next : Some ( ( box MyNode { value : 999998u32 , next : "));
+    assert!(t.contains("( box MyNode { value : 0u32 , next : None } ) ) } ) ) } ) )"));
+    assert!(t.ends_with("} ) ) } ) )"));
+    assert_eq!(t.matches("MyNode").count(), DEPTH);
+}
+
+fn tokenize_with_budget<T>(value: &T, budget: Budget) -> (String, self_tokenize_trait::Error)
+where
+    T: ?Sized + ToCustomTokens
+{
+    let mut t = String::new();
+    let options = Options {
+        budget,
+        ..Options::default()
+    };
+    let error = value.try_to_custom_tokens(&mut t, options).unwrap_err();
+    (t, error)
+}
+
+#[test]
+fn test_budget_bytes() {
+    let value = vec![
+        MyStyle {
+            color: "red".to_string(),
+            border: None
+        },
+        MyStyle {
+            color: "blue".to_string(),
+            border: Some("1px solid black".to_string())
+        },
+    ];
+    let (t, error) = tokenize_with_budget(
+        &value,
+        Budget {
+            max_bytes: Some(100),
+            ..Budget::default()
+        }
+    );
+
+    assert_eq!(t, "vec! [ MyStyle { color : \"red\" . to_string ( ) , border : None } , MyStyle { color : \"blue\" .");
+    assert_eq!(error.path(), "[1].color");
+    assert_eq!(error.kind(), &ErrorKind::BudgetExceeded { limit: Limit::Bytes(100) });
+    assert_eq!(error.to_string(), "field `[1].color` exceeds the budget of 100 bytes");
+}
+
+#[test]
+fn test_budget_tokens() {
+    let value: Vec<MyStyle> = (0..3)
+        .map(|i| MyStyle {
+            color: format!("#{:06x}", i),
+            border: None
+        })
+        .collect();
+    let (t, error) = tokenize_with_budget(
+        &value,
+        Budget {
+            max_tokens: Some(20),
+            ..Budget::default()
+        }
+    );
+
+    assert_eq!(t, "vec! [ MyStyle { color : \"#000000\" . to_string ( ) , border : None } , MyStyle { color");
+    assert_eq!(t.split(' ').count(), 20);
+    assert_eq!(error.to_string(), "field `[1]` exceeds the budget of 20 tokens");
+}
+
+#[test]
+fn test_budget_depth() {
+    let mut chain = None;
+    for value in 0..10 {
+        chain = Some(Box::new(MyNode { value, next: chain }));
+    }
+    let (t, error) = tokenize_with_budget(
+        &chain,
+        Budget {
+            max_depth: Some(2),
+            ..Budget::default()
+        }
+    );
+
+    assert_eq!(
+        t,
+        "Some ( ( box MyNode { value : 9u32 , next : Some ( ( box MyNode { value : 8u32 , next : Some ( ( box MyNode { value :"
+    );
+    assert_eq!(error.to_string(), "field `.next.next.value` exceeds the budget of 2 levels of nesting");
+}
+
+#[test]
+fn test_budget_depth_boundary() {
+    let value = vec![MyStyle {
+        color: "red".to_string(),
+        border: Some("1px solid black".to_string())
+    }];
+    let mut t = String::new();
+    let options = Options {
+        budget: Budget {
+            max_depth: Some(2),
+            ..Budget::default()
+        },
+        ..Options::default()
+    };
+
+    // `Vec` delegating to its slice, and `Option` to `String`, don't nest any deeper.
+    assert_eq!(value.try_to_custom_tokens(&mut t, options), Ok(()));
+    assert_eq!(
+        t,
+        "vec! [ MyStyle { color : \"red\" . to_string ( ) , border : Some ( \"1px solid black\" . to_string ( ) ) } , ]"
+    );
+
+    let (t, error) = tokenize_with_budget(
+        &value,
+        Budget {
+            max_depth: Some(1),
+            ..Budget::default()
+        }
+    );
+
+    assert_eq!(t, "vec! [ MyStyle { color :");
+    assert_eq!(error.to_string(), "field `[0].color` exceeds the budget of 1 levels of nesting");
+}
+
+#[test]
+fn test_estimate_size() {
+    let value: Vec<MyStyle> = (0..100)
+        .map(|i| MyStyle {
+            color: format!("#{:06x}", i),
+            border: Some(i.to_string())
+        })
+        .collect();
+    let mut t = String::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(
+        estimate_size(&value, Options::default()),
+        OutputSize {
+            bytes: t.len(),
+            tokens: t.split(' ').count(),
+            depth: 2
+        }
+    );
+}
+
+#[test]
+fn test_estimate_size_side_files() {
+    let dir = std::env::temp_dir().join(format!("self-tokenize-estimate-{}", std::process::id()));
+    let value = include_bytes!("fixtures/Quantum.png").to_vec();
+    let options = Options {
+        side_files: Some(SideFiles {
+            threshold: 1024,
+            ..SideFiles::new(&dir)
+        }),
+        ..Options::default()
+    };
+    let size = estimate_size(&value, options.clone());
+
+    assert!(!dir.exists());
+
+    let mut t = String::new();
+    value.to_custom_tokens_with(&mut Context::with_options(&mut t, options));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(size.bytes, t.len());
+}
+
+#[test]
+fn test_size_report() {
+    let value = MyExampleStruct {
+        foo: "Hello".to_string(),
+        bar: MyExampleTupleStruct("world!".to_string()),
+        baz: vec![
+            MyExampleEnum::FooVariant(MyExampleUnit),
+            MyExampleEnum::BarVariant {
+                unit: MyExampleUnit
+            },
+        ]
+    };
+    let report = size_report(&value, Options::default());
+    let mut t = String::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(report.root.bytes, t.len());
+    assert_eq!(report.types.iter().map(|size| size.bytes).sum::<usize>(), t.len());
+    assert!(report.to_string().starts_with(
+        "     bytes     tokens       %  path
+       227         39  100.0%  value
+        24          5   10.6%    .foo
+        50          8   22.0%    .bar
+        25          5   11.0%      .0
+       112         15   49.3%    .baz
+        99         10   43.6%      [*] (x2)
+        14          1    6.2%        .0
+        14          1    6.2%        .unit
+
+     bytes     tokens       %  type
+        71          8   31.3%  "
+    ));
+    assert_eq!(
+        report.types.iter().map(|size| (size.type_name, size.bytes, size.count)).collect::<Vec<_>>(),
+        vec![
+            (std::any::type_name::<MyExampleEnum>(), 71, 2),
+            (std::any::type_name::<MyExampleStruct>(), 41, 1),
+            (std::any::type_name::<String>(), 32, 2),
+            (std::any::type_name::<MyExampleUnit>(), 28, 2),
+            (std::any::type_name::<MyExampleTupleStruct>(), 25, 1),
+            (std::any::type_name::<str>(), 17, 2),
+            (std::any::type_name::<[MyExampleEnum]>(), 8, 1),
+            (std::any::type_name::<Vec<MyExampleEnum>>(), 5, 1),
+        ]
+    );
+    assert!(report.to_csv().starts_with(
+        "kind,name,bytes,tokens,count
+path,\"\",227,39,1
+path,\".foo\",24,5,1
+path,\".bar\",50,8,1
+path,\".bar.0\",25,5,1
+path,\".baz\",112,15,1
+path,\".baz[*]\",99,10,2
+path,\".baz[*].0\",14,1,1
+path,\".baz[*].unit\",14,1,1
+type,"
+    ));
+}
+
+#[test]
+fn test_trace() {
+    let value = MyExampleStruct {
+        foo: "Hello".to_string(),
+        bar: MyExampleTupleStruct("world!".to_string()),
+        baz: vec![
+            MyExampleEnum::FooVariant(MyExampleUnit),
+            MyExampleEnum::BarVariant {
+                unit: MyExampleUnit
+            },
+        ]
+    };
+    let trace = trace(&value, Options::default());
+    let mut t = String::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(trace.output, t);
+    assert_eq!(trace.root.span, 0..t.len());
+    assert_eq!(trace.root.impl_name, "#[derive(SelfTokenize)]");
+    let baz = &trace.root.children[2];
+    assert_eq!((baz.path.as_str(), baz.type_name), (".baz", std::any::type_name::<Vec<MyExampleEnum>>()));
+    let unit = &baz.children[0].children[1].children[0];
+    assert_eq!(unit.path, ".baz[1].unit");
+    assert_eq!(&t[unit.span.clone()], "MyExampleUnit");
+    assert_eq!(trace.root.find(unit.span.start), Some(unit));
+    assert_eq!(trace.root.find(t.len()), None);
+
+    let foo = &trace.root.children[0];
+    let lines = trace.to_string();
+    assert!(lines.contains(&format!(
+        "  .foo {} via impl for String [24..47]: \"Hello\" . to_string ( )\n    .foo str via default_impls! [24..31]: \"Hello\"\n",
+        foo.type_name
+    )));
+}
+
+#[test]
+fn test_trace_notes() {
+    let numbers = trace(&vec![1u32, 2, 3], Options::default());
+    assert_eq!(numbers.root.children[0].impl_name, "numeric_slice_impls!");
+    assert_eq!(&numbers.output[numbers.root.children[0].span.clone()], "[1, 2, 3]");
+
+    let options = Options {
+        compact_numbers: true,
+        ..Options::default()
+    };
+    let runs = trace(&vec![1u32; 40], options);
+    assert_eq!(runs.root.notes, vec!["repeated runs"]);
+    assert_eq!(runs.root.children.len(), 1);
+    assert_eq!(runs.root.children[0].path, "[0]");
+}
+
+#[test]
+fn test_source_map() {
+    let value = MyExampleStruct {
+        foo: "Hello".to_string(),
+        bar: MyExampleTupleStruct("world!".to_string()),
+        baz: vec![
+            MyExampleEnum::FooVariant(MyExampleUnit),
+            MyExampleEnum::BarVariant {
+                unit: MyExampleUnit
+            },
+        ]
+    };
+    let map = source_map(&value, Options::default());
+    let mut t = String::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(map.code, t);
+    assert_eq!(map.entries.len(), 12);
+    assert_eq!(map.entries[0].span, 0..t.len());
+    let unit = t.rfind("MyExampleUnit").unwrap();
+    assert_eq!(map.entry_at(unit).map(|entry| &entry.path[..]), Some(".baz[1].unit"));
+    assert_eq!(map.field_at(1, unit + 1), map.entry_at(unit));
+    let comma = t.find(", bar").unwrap();
+    assert_eq!(map.field_at(1, comma + 1).map(|entry| &entry.path[..]), Some(""));
+    assert_eq!(map.field_at(2, 1), None);
+    assert_eq!(map.field_at(1, t.len() + 1), None);
+}
+
+#[test]
+fn test_pretty_print() {
+    let value = MyExampleStruct {
+        foo: "Hello".to_string(),
+        bar: MyExampleTupleStruct("world!".to_string()),
+        baz: vec![
+            MyExampleEnum::FooVariant(MyExampleUnit),
+            MyExampleEnum::BarVariant {
+                unit: MyExampleUnit
+            },
+        ]
+    };
+    let mut t = String::new();
+    value.to_custom_tokens(&mut t);
+
+    assert_eq!(
+        pretty_print(&t, PrettyOptions::default()),
+        "MyExampleStruct {
+    foo: \"Hello\".to_string(),
+    bar: MyExampleTupleStruct(\"world!\".to_string()),
+    baz: vec![
+        MyExampleEnum::FooVariant(MyExampleUnit),
+        MyExampleEnum::BarVariant { unit: MyExampleUnit },
+    ],
+}"
+    );
+    let options = PrettyOptions {
+        max_width: 40,
+        trailing_commas: false,
+        ..PrettyOptions::default()
+    };
+    assert_eq!(
+        pretty_print(&t, options),
+        "MyExampleStruct {
+    foo: \"Hello\".to_string(),
+    bar: MyExampleTupleStruct(
+        \"world!\".to_string()
+    ),
+    baz: vec![
+        MyExampleEnum::FooVariant(
+            MyExampleUnit
+        ),
+        MyExampleEnum::BarVariant {
+            unit: MyExampleUnit
+        }
+    ]
+}"
+    );
+    let mut t = quote::Tokens::new();
+    MyRc(Rc::new(1)).to_custom_tokens(&mut t);
+    assert_eq!(pretty_print(t.as_str(), PrettyOptions::default()), "MyRc(::std::rc::Rc::new(1u8))");
+}
+
+#[test]
+fn test_pretty_print_lazy_static() {
+    let value = ((0..30).map(|i| i * 37 - 500).collect::<Vec<i32>>(), (1u8,), '\'', -1.5f64);
+    let mut t = quote::Tokens::new();
+    append_lazy_static(&mut Context::new(&mut t), "pub", "TABLE", &value);
+
+    assert_eq!(
+        pretty_print(t.as_str(), PrettyOptions::default()),
+        "pub static TABLE: ::std::sync::LazyLock<(Vec<i32>, (u8,), char, f64)> = ::std::sync::LazyLock::new(
+    || (
+        vec![
+            -500, -463, -426, -389, -352, -315, -278, -241, -204, -167, -130, -93, -56, -19, 18, 55,
+            92, 129, 166, 203, 240, 277, 314, 351, 388, 425, 462, 499, 536, 573,
+        ],
+        (1u8,),
+        '\\'',
+        (-1.5f64),
+    ),
+);"
+    );
+}
+
+#[test]
+fn test_pretty_print_grouping_parens() {
+    fn tokens<T: ?Sized + ToCustomTokens>(value: &T, options: &Options) -> quote::Tokens {
+        let mut t = quote::Tokens::new();
+        value.to_custom_tokens_with(&mut Context::with_options(&mut t, options.clone()));
+        t
+    }
+
+    let boxed_vec: Box<Vec<i64>> = Box::new((-12..0).collect());
+    let boxed_bytes: Box<[u8]> = b"a byte string longer than the line".to_vec().into_boxed_slice();
+    let negative = (-1234567890i64, -1234567890i64);
+    let map = (BTreeMap::from_iter(vec![(1u8, 2u8)]),);
+    let bytes: &[u8] = b"a byte string longer than the line";
+    let narrow = PrettyOptions {
+        max_width: 16,
+        ..PrettyOptions::default()
+    };
+
+    // Only lists get a trailing comma: in parentheses around a single
+    // expression, it would turn them into a tuple. Types, visibilities and the
+    // generic arguments inside them stay on one line. Targets 2018 so that
+    // boxes are emitted with `Box::new`.
+    let options = Options {
+        edition: Edition::Edition2018,
+        ..Options::default()
+    };
+    let boxed_vec_tokens = tokens(&boxed_vec, &options);
+    let boxed_bytes_tokens = tokens(&boxed_bytes, &options);
+    let negative_tokens = tokens(&negative, &options);
+    let map_tokens = tokens(&map, &options);
+    let bytes_tokens = tokens(bytes, &Options {
+        constant: true,
+        ..options.clone()
+    });
+    let items = [
+        quote! { pub fn boxed_vec() -> Box<Vec<i64>> { #boxed_vec_tokens } },
+        quote! { pub fn boxed_bytes() -> Box<[u8]> { #boxed_bytes_tokens } },
+        quote! { pub fn negative() -> (i64, i64) { #negative_tokens } },
+        quote! { pub(super) fn map() -> (::std::collections::BTreeMap<u8, u8>,) { #map_tokens } },
+        quote! { pub(super) static BYTES: &[u8] = &#bytes_tokens; },
+    ];
+    let source = fixture_source(items.iter().map(|item| pretty_print(item.as_str(), narrow)).collect());
+    check_fixture("pretty.rs", &source);
+
+    assert_eq!(pretty::boxed_vec(), boxed_vec);
+    assert_eq!(pretty::boxed_bytes(), boxed_bytes);
+    assert_eq!(pretty::negative(), negative);
+    assert_eq!(pretty::map(), map);
+    assert_eq!(pretty::BYTES, bytes);
+}
+
+#[allow(unused_parens, clippy::double_parens, clippy::box_collection, clippy::borrow_deref_ref)]
+mod pretty {
+    include!("fixtures/pretty.rs");
+}
+
+#[test]
+fn test_source_map_pretty() {
+    let value = MyExampleStruct {
+        foo: "Hello".to_string(),
+        bar: MyExampleTupleStruct("world!".to_string()),
+        baz: vec![
+            MyExampleEnum::FooVariant(MyExampleUnit),
+            MyExampleEnum::BarVariant {
+                unit: MyExampleUnit
+            },
+        ]
+    };
+    let options = PrettyOptions {
+        max_width: 40,
+        ..PrettyOptions::default()
+    };
+    let map = source_map(&value, Options::default()).pretty(options);
+
+    assert_eq!(map.code, pretty_print(&source_map(&value, Options::default()).code, options));
+    assert_eq!(map.field_at(11, 19).map(|entry| &entry.path[..]), Some(".baz[1].unit"));
+    assert_eq!(map.field_at(4, 9).map(|entry| &entry.path[..]), Some(".bar.0"));
+    assert_eq!(map.field_at(3, 10).map(|entry| &entry.path[..]), Some(".bar"));
+    assert_eq!(map.field_at(3, 5).map(|entry| &entry.path[..]), Some(""));
+    let bar = &map.entries.iter().find(|entry| entry.path == ".bar").unwrap();
+    assert_eq!(&map.code[bar.span.clone()], "MyExampleTupleStruct(\n        \"world!\".to_string(),\n    )");
+}
+
+#[test]
+fn test_source_file() {
+    let dir = std::env::temp_dir().join(format!("self-tokenize-source-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut file = SourceFile::new(dir.join("values.rs"));
+    file.options.rust_version = Some(RustVersion::new(1, 80));
+    file.input("data/values.json")
+        .add(ItemKind::Const, "pub", "ORIGIN", &MyTupleStructA(-1))
+        .add(ItemKind::Static, "", "NAME", &"name")
+        .add(ItemKind::Lazy, "pub(crate)", "LOOKUP", &MyStructC { foo: "foo".to_string() });
+
+    assert_eq!(file.write(), Ok(()));
+    assert_eq!(
+        std::fs::read_to_string(dir.join("values.rs")).unwrap(),
+        "// @generated by self-tokenize-trait. Do not edit.
+// Inputs: data/values.json
+
+#[allow(dead_code, non_upper_case_globals, unused_parens, clippy::all, clippy::pedantic)]
+pub const ORIGIN: MyTupleStructA = MyTupleStructA((-1i32));
+
+#[allow(dead_code, non_upper_case_globals, unused_parens, clippy::all, clippy::pedantic)]
+static NAME: &str = \"name\";
+
+#[allow(dead_code, non_upper_case_globals, unused_parens, clippy::all, clippy::pedantic)]
+pub(crate) static LOOKUP: ::std::sync::LazyLock<MyStructC> = ::std::sync::LazyLock::new(
+    || MyStructC { foo: String::from(\"foo\") },
+);
+"
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_source_file_error() {
+    let mut file = SourceFile::new("unused.rs");
+    file.add(ItemKind::Static, "pub", "TABLE", &MyStructC { foo: "foo".to_string() });
+
+    let error = file.to_source().unwrap_err();
+    assert_eq!(error.path(), "TABLE.foo");
+    assert_eq!(error.kind(), &ErrorKind::NotConst { type_name: std::any::type_name::<String>() });
+}
+
+#[test]
+fn test_source_file_output_compiles() {
+    fn to_source(pretty: Option<PrettyOptions>) -> String {
+        let mut file = SourceFile::new("unused.rs");
+        file.options.rust_version = Some(RustVersion::new(1, 80));
+        file.pretty = pretty;
+        file.add(ItemKind::Const, "pub", "ORIGIN", &MyTupleStructA(-1))
+            .add(ItemKind::Static, "pub", "NAMES", &["first", "second"])
+            .add(ItemKind::Lazy, "pub", "SIZES", &vec![MyTupleStructA(12), MyTupleStructA(16)])
+            .add(ItemKind::Fn, "pub", "preset", &(MyTupleStructA(7), "seven".to_string()));
+        file.to_source().unwrap()
+    }
+
+    check_fixture("source_file.rs", &to_source(Some(PrettyOptions::default())));
+    check_fixture("source_file_flat.rs", &to_source(None));
+
+    assert!(source_file::ORIGIN == MyTupleStructA(-1));
+    assert_eq!(source_file::NAMES, ["first", "second"]);
+    assert!(*source_file::SIZES == vec![MyTupleStructA(12), MyTupleStructA(16)]);
+    assert!(source_file::preset() == (MyTupleStructA(7), "seven".to_string()));
+    assert!(source_file_flat::ORIGIN == MyTupleStructA(-1));
+    assert_eq!(source_file_flat::NAMES, ["first", "second"]);
+    assert!(*source_file_flat::SIZES == vec![MyTupleStructA(12), MyTupleStructA(16)]);
+    assert!(source_file_flat::preset() == (MyTupleStructA(7), "seven".to_string()));
+}
+
+mod source_file {
+    use super::MyTupleStructA;
+    include!("fixtures/source_file.rs");
+}
+
+mod source_file_flat {
+    use super::MyTupleStructA;
+    include!("fixtures/source_file_flat.rs");
+}
+
+#[test]
+fn test_item_builders() {
+    #[derive(SelfTokenize, Debug, PartialEq)]
+    struct MyTheme {
+        name: &'static str,
+        accent: (u8, u8, u8),
+        bold: bool
+    }
+
+    let dark = MyTheme {
+        name: "dark",
+        accent: (30, 30, 46),
+        bold: false
+    };
+    let options = Options {
+        constant: true,
+        ..Options::default()
+    };
+    let mut t = String::new();
+    {
+        let mut ctx = Context::with_options(&mut t, options);
+        append_default_impl(&mut ctx, &dark);
+        append_constructor(&mut ctx, "pub", "preset_dark", &dark);
+        append_associated_const(&mut ctx, "pub", "DARK", &dark);
+        assert_eq!(ctx.finish(), Ok(()));
+    }
+
+    assert_eq!(
+        pretty_print(&t, PrettyOptions::default()),
+        "impl Default for MyTheme {
+    fn default() -> Self {
+        MyTheme { name: \"dark\", accent: (30u8, 30u8, 46u8), bold: false }
+    }
+}
+pub const fn preset_dark() -> MyTheme {
+    MyTheme { name: \"dark\", accent: (30u8, 30u8, 46u8), bold: false }
+}
+impl MyTheme {
+    pub const DARK: Self = MyTheme { name: \"dark\", accent: (30u8, 30u8, 46u8), bold: false };
+}"
+    );
+
+    impl Default for MyTheme {
+        fn default() -> Self {
+            MyTheme { name: "dark", accent: (30u8, 30u8, 46u8), bold: false }
+        }
+    }
+    pub const fn preset_dark() -> MyTheme {
+        MyTheme { name: "dark", accent: (30u8, 30u8, 46u8), bold: false }
+    }
+    impl MyTheme {
+        pub const DARK: Self = MyTheme { name: "dark", accent: (30u8, 30u8, 46u8), bold: false };
+    }
+    assert_eq!(MyTheme::default(), dark);
+    assert_eq!(preset_dark(), dark);
+    assert_eq!(MyTheme::DARK, dark);
+}
+
+#[test]
+fn test_source_file_item_builders() {
+    let mut file = SourceFile::new("unused.rs");
+    file.add(ItemKind::Fn, "pub", "preset_sizes", &vec![MyTupleStructA(12), MyTupleStructA(16)])
+        .add(ItemKind::DefaultImpl, "", "", &MyTupleStructA(7));
+
+    assert_eq!(
+        file.to_source(),
+        Ok("// @generated by self-tokenize-trait. Do not edit.
+
+#[allow(dead_code, non_upper_case_globals, unused_parens, clippy::all, clippy::pedantic)]
+pub fn preset_sizes() -> Vec<MyTupleStructA> {
+    vec![MyTupleStructA(12i32), MyTupleStructA(16i32)]
+}
+
+#[allow(dead_code, non_upper_case_globals, unused_parens, clippy::all, clippy::pedantic)]
+impl Default for MyTupleStructA {
+    fn default() -> Self {
+        MyTupleStructA(7i32)
+    }
+}
+"
+            .to_string())
+    );
+    file.add(ItemKind::AssociatedConst, "", "LIGHT", &MyStyle {
+        color: "white".to_string(),
+        border: None
+    });
+
+    let error = file.to_source().unwrap_err();
+    assert_eq!(error.path(), "LIGHT.color");
+    assert_eq!(error.kind(), &ErrorKind::NotConst { type_name: std::any::type_name::<String>() });
+}
+
+#[test]
+fn test_item_builders_generic() {
+    #[derive(SelfTokenize, Debug, PartialEq)]
+    struct MyPalette<'a, T: ToCustomTokens> {
+        name: &'a str,
+        accent: T
+    }
+
+    let warm = MyPalette {
+        name: "warm",
+        accent: 7u8
+    };
+    let options = Options {
+        constant: true,
+        ..Options::default()
+    };
+    let mut t = String::new();
+    {
+        let mut ctx = Context::with_options(&mut t, options);
+        append_default_impl(&mut ctx, &warm);
+        append_constructor(&mut ctx, "pub", "preset_warm", &warm);
+        append_associated_const(&mut ctx, "pub", "WARM", &warm);
+        assert_eq!(ctx.finish(), Ok(()));
+    }
+
+    assert_eq!(
+        pretty_print(&t, PrettyOptions::default()),
+        "impl Default for MyPalette<'static, u8> {
+    fn default() -> Self {
+        MyPalette { name: \"warm\", accent: 7u8 }
+    }
+}
+pub const fn preset_warm() -> MyPalette<'static, u8> {
+    MyPalette { name: \"warm\", accent: 7u8 }
+}
+impl MyPalette<'static, u8> {
+    pub const WARM: Self = MyPalette { name: \"warm\", accent: 7u8 };
+}"
+    );
+
+    impl Default for MyPalette<'static, u8> {
+        fn default() -> Self {
+            MyPalette { name: "warm", accent: 7u8 }
+        }
+    }
+    pub const fn preset_warm() -> MyPalette<'static, u8> {
+        MyPalette { name: "warm", accent: 7u8 }
+    }
+    impl MyPalette<'static, u8> {
+        pub const WARM: Self = MyPalette { name: "warm", accent: 7u8 };
+    }
+    assert_eq!(MyPalette::default(), warm);
+    assert_eq!(preset_warm(), warm);
+    assert_eq!(MyPalette::WARM, warm);
+}
+
+#[test]
+fn test_constructor_not_const() {
+    let value = MyStyle {
+        color: "white".to_string(),
+        border: None
+    };
+    let options = Options {
+        constant: true,
+        ..Options::default()
+    };
+    let mut t = String::new();
+    {
+        let mut ctx = Context::with_options(&mut t, options);
+        append_constructor(&mut ctx, "pub", "preset_light", &value);
+        assert_eq!(ctx.finish(), Ok(()));
+    }
+
+    assert_eq!(
+        pretty_print(&t, PrettyOptions::default()),
+        "pub fn preset_light() -> MyStyle {
+    MyStyle { color: \"white\".to_string(), border: None }
+}"
+    );
+}
+
+#[test]
+fn test_item_builders_unnamed_type() {
+    let mut t = String::new();
+    let mut ctx = Context::new(&mut t);
+    append_default_impl(&mut ctx, &MyHandWritten(1));
+
+    let error = ctx.finish().unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::UnnamedType { type_name: std::any::type_name::<MyHandWritten>() });
+
+    let mut file = SourceFile::new("unused.rs");
+    file.add(ItemKind::Static, "pub", "LEGACY", &MyHandWritten(1));
+
+    let error = file.to_source().unwrap_err();
+    assert_eq!(error.path(), "LEGACY");
+    assert_eq!(error.kind(), &ErrorKind::UnnamedType { type_name: std::any::type_name::<MyHandWritten>() });
+}
@@ -0,0 +1,110 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+// Compares how long rustc takes to compile a large numeric table emitted
+// element by element against the same table with `compact_numbers` set.
+// Run with `cargo bench -p self-tokenize-trait`; set `RUSTC` to pick a compiler.
+
+extern crate quote;
+extern crate self_tokenize_trait;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use self_tokenize_trait::{Context, Options, ToCustomTokens};
+
+const LEN: usize = 50_000;
+const RUNS: usize = 3;
+
+fn main() {
+    let dir = env::temp_dir().join(format!("self-tokenize-compile-time-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let u16s: Vec<u16> = (0..LEN).map(|i| (i * 7919) as u16).collect();
+    let u32s: Vec<u32> = (0..LEN).map(|i| (i as u32).wrapping_mul(2_654_435_761)).collect();
+    let f32s: Vec<f32> = (0..LEN).map(|i| i as f32 / 3.0).collect();
+
+    println!("{:<16} {:>12} {:>14} {:>14} {:>14}", "table", "naive bytes", "naive", "compact bytes", "compact");
+    bench(&dir, "u16", &u16s);
+    bench(&dir, "u32", &u32s);
+    bench(&dir, "f32", &f32s);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// Compares an array and a `Vec` returned from a function, both decoded at
+// runtime when compact. `constant` mode is left out, as it never compacts.
+fn bench<T>(dir: &Path, name: &str, table: &Vec<T>)
+where
+    [T]: ToCustomTokens,
+    Vec<T>: ToCustomTokens
+{
+    let item = format!("pub fn table() -> [{}; {}] {{ {{}} }}", name, LEN);
+    report(dir, &format!("fn [{}; N]", name), &item, |options| tokenize(&table[..], options));
+    let item = format!("pub fn table() -> Vec<{}> {{ {{}} }}", name);
+    report(dir, &format!("fn Vec<{}>", name), &item, |options| tokenize(table, options));
+}
+
+fn report<F>(dir: &Path, label: &str, item: &str, tokenize: F)
+where
+    F: Fn(Options) -> String
+{
+    let naive = tokenize(Options::default());
+    let compact = tokenize(Options {
+        compact_numbers: true,
+        ..Options::default()
+    });
+    println!(
+        "{:<16} {:>12} {:>14?} {:>14} {:>14?}",
+        label,
+        naive.len(),
+        compile(dir, &item.replace("{}", &naive)),
+        compact.len(),
+        compile(dir, &item.replace("{}", &compact))
+    );
+}
+
+fn tokenize<T>(value: &T, options: Options) -> String
+where
+    T: ?Sized + ToCustomTokens
+{
+    let mut tokens = quote::Tokens::new();
+    value.to_custom_tokens_with(&mut Context::with_options(&mut tokens, options));
+    tokens.to_string()
+}
+
+// Best of `RUNS` wall clock times for compiling `source` as a library.
+fn compile(dir: &Path, source: &str) -> Duration {
+    let file = dir.join("table.rs");
+    fs::write(&file, source).unwrap();
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            let output = Command::new(&rustc)
+                .args(["--crate-type", "lib", "--edition", "2021", "--out-dir"])
+                .arg(dir)
+                .arg(&file)
+                .output()
+                .unwrap();
+            let elapsed = start.elapsed();
+            if !output.status.success() {
+                panic!("rustc failed:\n{}", String::from_utf8_lossy(&output.stderr));
+            }
+            elapsed
+        })
+        .min()
+        .unwrap()
+}
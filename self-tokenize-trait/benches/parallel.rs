@@ -0,0 +1,79 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+// Compares sequential and parallel tokenization of multi-megabyte values.
+// Run with `cargo bench -p self-tokenize-trait --features parallel`.
+
+extern crate quote;
+extern crate rayon;
+extern crate self_tokenize_trait;
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use self_tokenize_trait::{Context, Options, ToCustomTokens};
+
+const LEN: usize = 200_000;
+const RUNS: usize = 3;
+
+fn main() {
+    let records: Vec<(String, Vec<f64>, Option<u32>)> = (0..LEN)
+        .map(|i| (format!("record {}", i), (0..8).map(|j| (i * j) as f64 / 7.0).collect(), Some(i as u32)))
+        .collect();
+    let index: BTreeMap<String, u64> = (0..LEN).map(|i| (format!("key {}", i), i as u64 * 31)).collect();
+
+    // The parallel path is skipped on a single thread, so the speedup depends on this.
+    println!("rayon threads: {}", rayon::current_num_threads());
+    println!("{:<28} {:>12} {:>14} {:>14} {:>8}", "value", "bytes", "sequential", "parallel", "speedup");
+    bench("Vec<(String, Vec<f64>, ..)>", &records);
+    bench("BTreeMap<String, u64>", &index);
+}
+
+fn bench<T>(label: &str, value: &T)
+where
+    T: ToCustomTokens
+{
+    let (sequential, sequential_time) = time(value, false);
+    let (parallel, parallel_time) = time(value, true);
+    assert!(sequential == parallel, "parallel output differs");
+    println!(
+        "{:<28} {:>12} {:>14?} {:>14?} {:>7.1}x",
+        label,
+        sequential.len(),
+        sequential_time,
+        parallel_time,
+        sequential_time.as_secs_f64() / parallel_time.as_secs_f64()
+    );
+}
+
+// Output and best of `RUNS` wall clock times.
+fn time<T>(value: &T, parallel: bool) -> (String, Duration)
+where
+    T: ToCustomTokens
+{
+    let options = Options {
+        parallel,
+        ..Options::default()
+    };
+    let mut output = String::new();
+    let elapsed = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            let mut tokens = String::new();
+            value.to_custom_tokens_with(&mut Context::with_options(&mut tokens, options.clone()));
+            let elapsed = start.elapsed();
+            output = tokens;
+            elapsed
+        })
+        .min()
+        .unwrap();
+    (output, elapsed)
+}
@@ -0,0 +1,76 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::fmt;
+
+use context::{Context, Options};
+use sink::Discard;
+use ToCustomTokens;
+
+/// Limits on the output of a single tokenization. Once one is exceeded,
+/// nothing more is emitted and `Context::finish` reports where it happened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Budget {
+    pub max_bytes: Option<usize>,
+    pub max_tokens: Option<usize>,
+    /// Maximum nesting depth of the fields and elements below the value.
+    pub max_depth: Option<usize>
+}
+
+impl Budget {
+    pub fn is_unlimited(&self) -> bool {
+        *self == Budget::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Bytes(usize),
+    Tokens(usize),
+    Depth(usize)
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Limit::Bytes(bytes) => write!(f, "{} bytes", bytes),
+            Limit::Tokens(tokens) => write!(f, "{} tokens", tokens),
+            Limit::Depth(depth) => write!(f, "{} levels of nesting", depth)
+        }
+    }
+}
+
+/// Size of the output emitted through a `Context`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutputSize {
+    /// Bytes, counting one space between tokens.
+    pub bytes: usize,
+    /// Appended tokens. A literal counts as one, even a numeric slice emitted
+    /// as `[1, 2, 3]`.
+    pub tokens: usize,
+    /// Deepest nesting of fields and elements.
+    pub depth: usize
+}
+
+/// Runs the tokenization of `value` without keeping any of its output, so
+/// that callers can pick options up front, e.g. `compact_numbers` or a
+/// `WriteSink` for large values. Side files are counted as `include_bytes!`
+/// but not written.
+pub fn estimate_size<T>(value: &T, options: Options) -> OutputSize
+where
+    T: ?Sized + ToCustomTokens
+{
+    let mut discard = Discard;
+    let mut ctx = Context::with_options(&mut discard, options);
+    ctx.skip_file_writes();
+    value.to_custom_tokens_with(&mut ctx);
+    ctx.output_size()
+}
@@ -0,0 +1,94 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::ascii;
+use std::fs;
+use std::io;
+use std::iter;
+use std::path::PathBuf;
+
+use context::Context;
+use error::ErrorKind;
+
+// Bytes escaped at a time, so that a large literal streams to the sink.
+const PART_LEN: usize = 64 * 1024;
+
+/// Emits an expression of type `&'static [u8; N]`: a byte-string literal, or an
+/// `include_bytes!` of a side file when the buffer exceeds `SideFiles::threshold`.
+pub fn append_byte_string(ctx: &mut Context, bytes: &[u8]) {
+    let side_files = match ctx.options().side_files {
+        Some(ref side_files) if bytes.len() >= side_files.threshold => Some(side_files.clone()),
+        _ => None
+    };
+    if let Some(side_files) = side_files {
+        let file_name = format!("{:016x}.bin", content_hash(bytes));
+        let file = side_files.dir.join(&file_name);
+        let written = if ctx.writes_files() {
+            write_side_file(&file, bytes)
+        } else {
+            Ok(())
+        };
+        match written {
+            Ok(()) => {
+                ctx.trace_note("side file");
+                ctx.add_generated_file(file);
+                ctx.append_prelude_path("include_bytes!", "::std::include_bytes!");
+                ctx.append("(");
+                ctx.append_prelude_path("concat!", "::std::concat!");
+                ctx.append("(");
+                ctx.append_prelude_path("env!", "::std::env!");
+                ctx.append("(");
+                ctx.append(format!("{:?}", side_files.env_var));
+                ctx.append(")");
+                ctx.append(",");
+                ctx.append(format!("\"/{}\"", file_name));
+                ctx.append(")");
+                ctx.append(")");
+                return;
+            }
+            Err(error) => ctx.fail(ErrorKind::Io {
+                file,
+                message: error.to_string()
+            })
+        }
+    }
+
+    ctx.trace_note("byte string");
+    let parts = bytes.chunks(PART_LEN).map(|chunk| {
+        let mut part = String::with_capacity(chunk.len());
+        chunk
+            .iter()
+            .for_each(|&byte| part.extend(ascii::escape_default(byte).map(char::from)));
+        part
+    });
+    ctx.append_parts(iter::once("b\"".to_string()).chain(parts).chain(iter::once("\"".to_string())));
+}
+
+fn write_side_file(file: &PathBuf, bytes: &[u8]) -> io::Result<()> {
+    // Files are named after their contents, so an existing one is left untouched
+    // to keep its mtime, and with it cargo's rebuild checks, stable.
+    match fs::metadata(file) {
+        Ok(ref metadata) if metadata.len() == bytes.len() as u64 => Ok(()),
+        _ => {
+            if let Some(dir) = file.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(file, bytes)
+        }
+    }
+}
+
+// 64-bit FNV-1a.
+fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -0,0 +1,67 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use context::Context;
+use {ToCustomTokens, ToVecTokens};
+
+/// Emits `items` as one `fn part_N() -> Vec<T>` per chunk, and a block that
+/// appends the parts together. Unless the parts are being collected into a
+/// module, their functions are declared at the start of that block.
+pub fn append_chunked_vec<T>(ctx: &mut Context, items: &[T], chunk_size: usize)
+where
+    T: ToCustomTokens
+{
+    ctx.trace_note("chunked");
+    ctx.append("{");
+    let names: Vec<String> = items
+        .chunks(chunk_size)
+        .map(|chunk| {
+            ctx.append_part(|ctx, name| {
+                ctx.append("fn");
+                ctx.append(name);
+                ctx.append("(");
+                ctx.append(")");
+                ctx.append("->");
+                ctx.append_item_type::<Vec<T>>();
+                ctx.append("{");
+                chunk.to_vec_tokens(ctx);
+                ctx.append("}");
+            })
+        })
+        .collect();
+
+    ctx.append("let");
+    ctx.append("mut");
+    ctx.append("items");
+    ctx.append("=");
+    ctx.append_prelude_path("Vec", "::std::vec::Vec");
+    ctx.append("::");
+    ctx.append("with_capacity");
+    ctx.append("(");
+    ctx.append(items.len().to_string());
+    ctx.append(")");
+    ctx.append(";");
+    names.iter().for_each(|name| {
+        ctx.append("items");
+        ctx.append(".");
+        ctx.append("append");
+        ctx.append("(");
+        ctx.append("&");
+        ctx.append("mut");
+        ctx.append(name);
+        ctx.append("(");
+        ctx.append(")");
+        ctx.append(")");
+        ctx.append(";");
+    });
+    ctx.append("items");
+    ctx.append("}");
+}
@@ -0,0 +1,259 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use bytes::append_byte_string;
+use context::{Context, Options};
+
+/// Numeric slices shorter than this are cheaper to emit element by element
+/// than to decode.
+pub const MIN_COMPACT_LEN: usize = 16;
+
+pub trait Compact {
+    /// Whether `options` ask for the slice to be emitted as a byte string plus
+    /// a `from_le_bytes` decode, and the target can decode it. Never in
+    /// `constant` mode, where rustc takes several times longer to evaluate the
+    /// decode than to compile the elements one by one.
+    fn is_compact(&self, options: &Options) -> bool;
+
+    /// Emits an expression of type `[T; N]`, or `Vec<T>` when `into_vec` is set.
+    fn append_compact(&self, ctx: &mut Context, into_vec: bool);
+}
+
+impl<T> Compact for [T] {
+    default fn is_compact(&self, _options: &Options) -> bool {
+        false
+    }
+
+    default fn append_compact(&self, _ctx: &mut Context, _into_vec: bool) {
+        unreachable!()
+    }
+}
+
+// Widths and byte orders are spelled out in the output, so `isize` and `usize`,
+// whose width depends on the target, are left out.
+macro_rules! compact_impls {
+    ( $( $ty:ident: $width:expr, $bits:ident; )+ ) => { $(
+        impl Compact for [$ty] {
+            fn is_compact(&self, options: &Options) -> bool {
+                options.compact_numbers && !options.constant && self.len() >= MIN_COMPACT_LEN && options.supports(1, 32)
+            }
+
+            fn append_compact(&self, ctx: &mut Context, into_vec: bool) {
+                ctx.trace_note("compact numbers");
+                let mut bytes = Vec::with_capacity(self.len() * $width);
+                self.iter().for_each(|item| bytes.extend_from_slice(&item.to_bits().to_le_bytes()));
+                let decode = Decode {
+                    ty: stringify!($ty),
+                    bits: stringify!($bits),
+                    width: $width,
+                    len: self.len()
+                };
+                decode.append(ctx, &bytes, into_vec);
+            }
+        }
+    )+ }
+}
+
+trait ToBits {
+    type Bits;
+
+    fn to_bits(&self) -> Self::Bits;
+}
+
+macro_rules! to_bits_impls {
+    ( $( $ty:ident: $bits:ident )+ ) => { $(
+        impl ToBits for $ty {
+            type Bits = $bits;
+
+            fn to_bits(&self) -> $bits {
+                *self
+            }
+        }
+    )+ }
+}
+
+to_bits_impls! {
+    u16: u16 i16: i16 u32: u32 i32: i32 u64: u64 i64: i64
+}
+
+impl ToBits for f32 {
+    type Bits = u32;
+
+    fn to_bits(&self) -> u32 {
+        f32::to_bits(*self)
+    }
+}
+
+impl ToBits for f64 {
+    type Bits = u64;
+
+    fn to_bits(&self) -> u64 {
+        f64::to_bits(*self)
+    }
+}
+
+compact_impls! {
+    u16: 2, u16;
+    i16: 2, i16;
+    u32: 4, u32;
+    i32: 4, i32;
+    u64: 8, u64;
+    i64: 8, i64;
+    f32: 4, u32;
+    f64: 8, u64;
+}
+
+struct Decode {
+    ty: &'static str,
+    bits: &'static str,
+    width: usize,
+    len: usize
+}
+
+impl Decode {
+    /// Emits the bytes as a little-endian constant and a decoding function that
+    /// runs when the expression is evaluated, filling the `Vec` directly for
+    /// `into_vec`.
+    fn append(&self, ctx: &mut Context, bytes: &[u8], into_vec: bool) {
+        ctx.append("{");
+        ctx.append("const");
+        ctx.append("BYTES");
+        ctx.append(":");
+        ctx.append("&");
+        ctx.append("[");
+        ctx.append("u8");
+        ctx.append("]");
+        ctx.append("=");
+        append_byte_string(ctx, bytes);
+        ctx.append(";");
+        ctx.append("fn");
+        ctx.append("decode");
+        ctx.append("(");
+        ctx.append(")");
+        ctx.append("->");
+        if into_vec {
+            self.append_vec_type(ctx);
+        } else {
+            self.append_array_type(ctx);
+        }
+        ctx.append("{");
+        ctx.append("let");
+        ctx.append("mut");
+        ctx.append("items");
+        ctx.append("=");
+        if into_vec {
+            ctx.append_prelude_path("Vec", "::std::vec::Vec");
+            ctx.append("::");
+            ctx.append("with_capacity");
+            ctx.append("(");
+            ctx.append(self.len.to_string());
+            ctx.append(")");
+        } else {
+            ctx.append("[");
+            ctx.append(format!("0{}", self.ty));
+            ctx.append(";");
+            ctx.append(self.len.to_string());
+            ctx.append("]");
+        }
+        ctx.append(";");
+        ctx.append("let");
+        ctx.append("mut");
+        ctx.append("i");
+        ctx.append("=");
+        ctx.append("0");
+        ctx.append(";");
+        ctx.append("while");
+        ctx.append("i");
+        ctx.append("<");
+        ctx.append(self.len.to_string());
+        ctx.append("{");
+        ctx.append("let");
+        ctx.append("at");
+        ctx.append("=");
+        ctx.append("i");
+        ctx.append("*");
+        ctx.append(self.width.to_string());
+        ctx.append(";");
+        if into_vec {
+            ctx.append("items");
+            ctx.append(".");
+            ctx.append("push");
+            ctx.append("(");
+            self.append_from_le_bytes(ctx);
+            ctx.append(")");
+        } else {
+            ctx.append("items");
+            ctx.append("[");
+            ctx.append("i");
+            ctx.append("]");
+            ctx.append("=");
+            self.append_from_le_bytes(ctx);
+        }
+        ctx.append(";");
+        ctx.append("i");
+        ctx.append("+=");
+        ctx.append("1");
+        ctx.append(";");
+        ctx.append("}");
+        ctx.append("items");
+        ctx.append("}");
+        ctx.append("decode");
+        ctx.append("(");
+        ctx.append(")");
+        ctx.append("}");
+    }
+
+    fn append_array_type(&self, ctx: &mut Context) {
+        ctx.append("[");
+        ctx.append(self.ty);
+        ctx.append(";");
+        ctx.append(self.len.to_string());
+        ctx.append("]");
+    }
+
+    fn append_vec_type(&self, ctx: &mut Context) {
+        ctx.append_prelude_path("Vec", "::std::vec::Vec");
+        ctx.append("<");
+        ctx.append(self.ty);
+        ctx.append(">");
+    }
+
+    fn append_from_le_bytes(&self, ctx: &mut Context) {
+        let is_float = self.ty != self.bits;
+        if is_float {
+            ctx.append(self.ty);
+            ctx.append("::");
+            ctx.append("from_bits");
+            ctx.append("(");
+        }
+        ctx.append(self.bits);
+        ctx.append("::");
+        ctx.append("from_le_bytes");
+        ctx.append("(");
+        ctx.append("[");
+        (0..self.width).for_each(|offset| {
+            ctx.append("BYTES");
+            ctx.append("[");
+            ctx.append("at");
+            if offset > 0 {
+                ctx.append("+");
+                ctx.append(offset.to_string());
+            }
+            ctx.append("]");
+            ctx.append(",");
+        });
+        ctx.append("]");
+        ctx.append(")");
+        if is_float {
+            ctx.append(")");
+        }
+    }
+}
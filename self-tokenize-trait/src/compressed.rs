@@ -0,0 +1,167 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::OnceLock;
+
+use miniz_oxide::deflate::compress_to_vec;
+use miniz_oxide::inflate::decompress_to_vec;
+
+use bytes::append_byte_string;
+use context::Context;
+use ToCustomTokens;
+
+const COMPRESSION_LEVEL: u8 = 9;
+
+/// Values that can be stored as a compressed byte payload.
+pub trait Payload: Sized {
+    fn to_payload(&self) -> Cow<'_, [u8]>;
+
+    fn from_payload(bytes: Vec<u8>) -> Self;
+}
+
+impl Payload for Vec<u8> {
+    fn to_payload(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self)
+    }
+
+    fn from_payload(bytes: Vec<u8>) -> Self {
+        bytes
+    }
+}
+
+impl Payload for String {
+    fn to_payload(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.as_bytes())
+    }
+
+    fn from_payload(bytes: Vec<u8>) -> Self {
+        String::from_utf8(bytes).expect("compressed payload is not valid UTF-8")
+    }
+}
+
+/// A value that is tokenized as a DEFLATE compressed `static` blob, and only
+/// decompressed the first time it is dereferenced. The emitted code calls
+/// `from_compressed`, so it needs this crate with `compress` at runtime.
+pub struct Compressed<T> {
+    compressed: Option<&'static [u8]>,
+    value: OnceLock<T>
+}
+
+impl<T> Compressed<T>
+where
+    T: Payload
+{
+    pub fn new(value: T) -> Self {
+        Compressed {
+            compressed: None,
+            value: OnceLock::from(value)
+        }
+    }
+
+    /// Used by the emitted code. `bytes` must be a raw DEFLATE stream.
+    pub const fn from_compressed(bytes: &'static [u8]) -> Self {
+        Compressed {
+            compressed: Some(bytes),
+            value: OnceLock::new()
+        }
+    }
+
+    pub fn get(&self) -> &T {
+        self.value.get_or_init(|| {
+            let bytes = decompress_to_vec(self.compressed.unwrap()).expect("corrupt compressed payload");
+            T::from_payload(bytes)
+        })
+    }
+
+    pub fn into_inner(self) -> T {
+        self.get();
+        self.value.into_inner().unwrap()
+    }
+}
+
+impl<T> Deref for Compressed<T>
+where
+    T: Payload
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.get()
+    }
+}
+
+impl<T> Clone for Compressed<T>
+where
+    T: Clone
+{
+    fn clone(&self) -> Self {
+        Compressed {
+            compressed: self.compressed,
+            value: self.value.clone()
+        }
+    }
+}
+
+impl<T> PartialEq for Compressed<T>
+where
+    T: Payload + PartialEq
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.get() == other.get()
+    }
+}
+
+impl<T> fmt::Debug for Compressed<T>
+where
+    T: Payload + fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Compressed").field(self.get()).finish()
+    }
+}
+
+impl<T> ToCustomTokens for Compressed<T>
+where
+    T: Payload + ToCustomTokens
+{
+    fn to_custom_tokens_with(&self, ctx: &mut Context) {
+        ctx.append("::self_tokenize_trait::Compressed");
+        if ctx.options().typed {
+            ctx.append("::");
+            ctx.append("<");
+            ctx.append_type::<T>();
+            ctx.append(">");
+        }
+        ctx.append("::");
+        ctx.append("from_compressed");
+        ctx.append("(");
+        match (self.compressed, self.value.get()) {
+            (Some(compressed), _) => append_byte_string(ctx, compressed),
+            (None, Some(value)) => append_byte_string(ctx, &compress_to_vec(&value.to_payload(), COMPRESSION_LEVEL)),
+            (None, None) => unreachable!()
+        }
+        ctx.append(")");
+    }
+
+    fn to_type_tokens(ctx: &mut Context) {
+        ctx.append("::self_tokenize_trait::Compressed");
+        ctx.append("<");
+        ctx.append_type::<T>();
+        ctx.append(">");
+    }
+
+    fn impl_name() -> &'static str {
+        "impl for Compressed<T>"
+    }
+}
@@ -0,0 +1,751 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::any::type_name;
+use std::fmt::Write;
+use std::mem;
+use std::path::PathBuf;
+
+use quote::Tokens;
+
+use budget::{Budget, Limit, OutputSize};
+use dedup::{binding_name, binding_reference, Dedup, Emitting, Plan, Recording, Step, MIN_DEDUP_LEN};
+use error::{Error, ErrorKind};
+use report::{Profile, SizeReport};
+use trace::{TraceNode, Tracer};
+use sink::TokenSink;
+use ToCustomTokens;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Edition {
+    #[default]
+    Edition2015,
+    Edition2018,
+    Edition2021,
+    Edition2024
+}
+
+impl Edition {
+    pub fn first_version(&self) -> RustVersion {
+        match *self {
+            Edition::Edition2015 => RustVersion::new(1, 0),
+            Edition::Edition2018 => RustVersion::new(1, 31),
+            Edition::Edition2021 => RustVersion::new(1, 56),
+            Edition::Edition2024 => RustVersion::new(1, 85)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RustVersion {
+    pub major: u32,
+    pub minor: u32
+}
+
+impl RustVersion {
+    pub fn new(major: u32, minor: u32) -> Self {
+        RustVersion { major, minor }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SideFiles {
+    /// Directory the files are written to, e.g. the value of `OUT_DIR` in a build script.
+    pub dir: PathBuf,
+    /// Environment variable that points at `dir` when the output is compiled.
+    pub env_var: String,
+    /// Byte buffers at least this large are written out instead of inlined.
+    pub threshold: usize
+}
+
+impl SideFiles {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        SideFiles {
+            dir: dir.into(),
+            env_var: "OUT_DIR".to_string(),
+            threshold: 64 * 1024
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Options {
+    /// Emit explicitly typed constructors (`Vec::<u8>::new()`, `None::<String>`)
+    /// so that the output does not rely on the surrounding code for inference.
+    pub typed: bool,
+    /// Spell out prelude items (`::std::option::Option::Some`, `::std::vec!`)
+    /// so that the output compiles under `#![no_implicit_prelude]`.
+    pub no_implicit_prelude: bool,
+    /// Edition of the crate the output is compiled in.
+    pub edition: Edition,
+    /// Minimum supported Rust version of the crate the output is compiled in.
+    /// Leaving both this and `edition` unset keeps the historic nightly idioms
+    /// (`box`, `vec!` plus `from_iter`) this crate has always emitted.
+    pub rust_version: Option<RustVersion>,
+    /// Only emit expressions that can be evaluated in a `const` or `static`
+    /// initializer. Values that cannot be are reported by `Context::finish`.
+    pub constant: bool,
+    /// Write large byte buffers to content-addressed files and emit them as
+    /// `include_bytes!`. See `Context::generated_files`.
+    pub side_files: Option<SideFiles>,
+    /// Emit large numeric slices as a little-endian byte string decoded with
+    /// `from_le_bytes`, rather than one literal per element. Ignored in
+    /// `constant` mode.
+    pub compact_numbers: bool,
+    /// Split `Vec`s longer than this into `fn part_N() -> Vec<T>` helpers of at
+    /// most this many elements each, so that rustc sees many small functions
+    /// instead of one giant literal. See `append_chunked_module`.
+    pub chunk_size: Option<usize>,
+    /// Limits on the size of the output. See `estimate_size` to check a value
+    /// up front instead.
+    pub budget: Budget,
+    /// Tokenize the elements of large collections on the rayon thread pool.
+    /// The output is the same as without it. Ignored unless the `parallel`
+    /// cargo feature is enabled, and while the pool has a single thread.
+    pub parallel: bool
+}
+
+impl Options {
+    pub fn target_version(&self) -> Option<RustVersion> {
+        match (self.edition, self.rust_version) {
+            (Edition::Edition2015, None) => None,
+            (edition, None) => Some(edition.first_version()),
+            (edition, Some(version)) => Some(version.max(edition.first_version()))
+        }
+    }
+
+    /// Whether an API stabilized in `major.minor` may be used. Without a
+    /// target, every API is assumed to be available.
+    pub fn supports(&self, major: u32, minor: u32) -> bool {
+        self.target_version()
+            .map_or(true, |version| version >= RustVersion::new(major, minor))
+    }
+
+    /// Whether to switch to an idiom introduced in `major.minor`. Only targets
+    /// that were set do, so that the default output stays the same.
+    pub(crate) fn prefers(&self, major: u32, minor: u32) -> bool {
+        self.target_version()
+            .map_or(false, |version| version >= RustVersion::new(major, minor))
+    }
+}
+
+// Stack left when a new segment is allocated, enough for the deepest chain of
+// frames between two calls to `tokenize`, and the size of each new segment.
+#[cfg(feature = "stacker")]
+const RED_ZONE: usize = 128 * 1024;
+#[cfg(feature = "stacker")]
+const STACK_SIZE: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Segment {
+    Field(&'static str),
+    Index(usize)
+}
+
+pub struct Context<'a> {
+    sink: &'a mut dyn TokenSink,
+    // Innermost last. While any buffer is open, tokens go there instead of the sink.
+    buffers: Vec<Tokens>,
+    options: Options,
+    path: Vec<Segment>,
+    error: Option<Error>,
+    generated_files: Vec<PathBuf>,
+    // Cleared for estimates, which must leave the file system alone.
+    writes_files: bool,
+    parts: Option<Vec<Tokens>>,
+    next_part: usize,
+    dedup: Option<Dedup>,
+    size: OutputSize,
+    // Set once the budget is exceeded, after which nothing more is emitted.
+    exhausted: bool,
+    profile: Option<Profile>,
+    tracer: Option<Tracer>
+}
+
+impl<'a> Context<'a> {
+    pub fn new(sink: &'a mut dyn TokenSink) -> Self {
+        Context::with_options(sink, Options::default())
+    }
+
+    pub fn with_options(sink: &'a mut dyn TokenSink, options: Options) -> Self {
+        Context {
+            sink,
+            buffers: vec![],
+            options,
+            path: vec![],
+            error: None,
+            generated_files: vec![],
+            writes_files: true,
+            parts: None,
+            next_part: 0,
+            dedup: None,
+            size: OutputSize::default(),
+            exhausted: false,
+            profile: None,
+            tracer: None
+        }
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    pub fn append<T: AsRef<str>>(&mut self, token: T) {
+        let token = token.as_ref();
+        if !token.is_empty() {
+            self.count(token.len());
+        }
+        self.append_buffered(token);
+    }
+
+    /// Appends output that was already counted when it was emitted into a buffer.
+    pub(crate) fn append_buffered<T: AsRef<str>>(&mut self, tokens: T) {
+        if self.exhausted {
+            return;
+        }
+        match self.buffers.last_mut() {
+            Some(buffer) => buffer.append(tokens),
+            None => self.sink.append_token(tokens.as_ref())
+        }
+    }
+
+    /// Appends one long token, such as a literal, produced in `parts`. Unless a
+    /// buffer is open, the parts go to the sink as they come, so the token is
+    /// never held whole. A token that overruns `max_bytes` is cut off there.
+    pub(crate) fn append_parts<I>(&mut self, parts: I)
+    where
+        I: Iterator<Item = String>
+    {
+        if !self.buffers.is_empty() {
+            let token: String = parts.collect();
+            self.append(token);
+            return;
+        }
+        let budget = self.options.budget;
+        if self.exhausted || matches!(budget.max_tokens, Some(max) if self.size.tokens >= max) {
+            let len = parts.map(|part| part.len()).sum();
+            self.count(len);
+            return;
+        }
+        let separator = if self.size.tokens > 0 { 1 } else { 0 };
+        let remaining = budget
+            .max_bytes
+            .map_or(usize::MAX, |max| max.saturating_sub(self.size.bytes + separator));
+        let mut len = 0;
+        {
+            let mut parts = parts.take_while(|part| {
+                len += part.len();
+                len <= remaining
+            });
+            self.sink.append_token_parts(&mut parts);
+        }
+        self.count(len);
+    }
+
+    fn count(&mut self, len: usize) {
+        if self.size.tokens > 0 {
+            self.size.bytes += 1;
+        }
+        self.size.bytes += len;
+        self.size.tokens += 1;
+        let budget = self.options.budget;
+        match (budget.max_bytes, budget.max_tokens) {
+            (Some(max), _) if self.size.bytes > max => self.exhaust(Limit::Bytes(max)),
+            (_, Some(max)) if self.size.tokens > max => self.exhaust(Limit::Tokens(max)),
+            _ => {}
+        }
+    }
+
+    // The dry run of `record_dedup` is not held to the budget.
+    fn exhaust(&mut self, limit: Limit) {
+        if !self.exhausted && !matches!(self.dedup, Some(Dedup::Recording(_))) {
+            self.fail(ErrorKind::BudgetExceeded { limit });
+            self.exhausted = true;
+        }
+    }
+
+    /// Size of the output so far.
+    pub fn output_size(&self) -> OutputSize {
+        self.size
+    }
+
+    fn size_since(&self, start: OutputSize) -> OutputSize {
+        OutputSize {
+            bytes: self.size.bytes - start.bytes,
+            tokens: self.size.tokens - start.tokens,
+            depth: 0
+        }
+    }
+
+    pub fn append_prelude_path(&mut self, name: &str, path: &str) {
+        if self.options.no_implicit_prelude {
+            self.append(path);
+        } else {
+            self.append(name);
+        }
+    }
+
+    pub fn append_type<T>(&mut self)
+    where
+        T: ?Sized + ToCustomTokens
+    {
+        T::to_type_tokens(self);
+    }
+
+    /// Like `append_type`, for items such as `static`s whose type must be
+    /// spelled out in full. Reports `ErrorKind::UnnamedType` if it is not.
+    pub(crate) fn append_item_type<T>(&mut self)
+    where
+        T: ?Sized + ToCustomTokens
+    {
+        let tokens = self.with_buffer(|ctx| ctx.append_type::<T>());
+        if tokens.as_str().split(' ').any(|token| token == "_") {
+            self.fail(ErrorKind::UnnamedType {
+                type_name: type_name::<T>()
+            });
+        }
+        self.append_buffered(tokens);
+    }
+
+    /// Emits `value`. Every impl recurses through here, so with the `stacker`
+    /// feature this is where the stack is grown onto the heap as it runs out,
+    /// and nesting depth is only limited by memory.
+    pub fn tokenize<T>(&mut self, value: &T)
+    where
+        T: ?Sized + ToCustomTokens
+    {
+        // Only fields and elements nest, not impls delegating to one another.
+        let depth = self.path.len();
+        self.size.depth = self.size.depth.max(depth);
+        match self.options.budget.max_depth {
+            Some(max) if depth > max => self.exhaust(Limit::Depth(max)),
+            _ => {}
+        }
+        if !self.exhausted {
+            let start = self.size;
+            if let Some(ref mut profile) = self.profile {
+                profile.begin_value();
+            }
+            if let Some(path) = self.tracer.as_ref().map(|_| self.path()) {
+                self.tracer.as_mut().unwrap().begin::<T>(path);
+            }
+            #[cfg(feature = "stacker")]
+            stacker::maybe_grow(RED_ZONE, STACK_SIZE, || self.tokenize_here(value));
+            #[cfg(not(feature = "stacker"))]
+            self.tokenize_here(value);
+            let size = self.size_since(start);
+            if let Some(ref mut profile) = self.profile {
+                profile.end_value::<T>(size);
+            }
+            if let Some(ref mut tracer) = self.tracer {
+                // Spans start after the separator in front of the first token.
+                let separator = if start.tokens > 0 && size.tokens > 0 { 1 } else { 0 };
+                tracer.end(start.bytes + separator..self.size.bytes);
+            }
+        }
+    }
+
+    fn tokenize_here<T>(&mut self, value: &T)
+    where
+        T: ?Sized + ToCustomTokens
+    {
+        match self.dedup {
+            None => value.to_custom_tokens_with(self),
+            Some(Dedup::Recording(_)) => self.tokenize_recording(value),
+            Some(Dedup::Emitting(_)) => self.tokenize_emitting(value)
+        }
+    }
+
+    fn tokenize_recording<T>(&mut self, value: &T)
+    where
+        T: ?Sized + ToCustomTokens
+    {
+        let visit = match self.dedup {
+            Some(Dedup::Recording(ref mut recording)) => recording.begin(),
+            _ => unreachable!()
+        };
+        let start = self.buffer().len();
+        value.to_custom_tokens_with(self);
+        let type_text = if self.buffer().len() - start >= MIN_DEDUP_LEN {
+            self.with_buffer(|ctx| ctx.append_type::<T>())
+        } else {
+            Tokens::new()
+        };
+        match self.dedup {
+            Some(Dedup::Recording(ref mut recording)) => {
+                recording.end::<T>(visit, &self.buffers.last().unwrap().as_str()[start..], type_text.as_str())
+            }
+            _ => unreachable!()
+        }
+    }
+
+    fn tokenize_emitting<T>(&mut self, value: &T)
+    where
+        T: ?Sized + ToCustomTokens
+    {
+        let (step, constant) = match self.dedup {
+            Some(Dedup::Emitting(ref mut emitting)) => (emitting.next_step(), emitting.is_constant()),
+            _ => unreachable!()
+        };
+        let binding = match step {
+            Step::Plain => return value.to_custom_tokens_with(self),
+            Step::Define(binding) => {
+                let definition = self.with_buffer(|ctx| {
+                    ctx.append(if constant { "const" } else { "let" });
+                    ctx.append(binding_name(binding, constant));
+                    ctx.append(":");
+                    ctx.append_type::<T>();
+                    ctx.append("=");
+                    value.to_custom_tokens_with(ctx);
+                    ctx.append(";");
+                });
+                match self.dedup {
+                    Some(Dedup::Emitting(ref mut emitting)) => emitting.define(definition),
+                    _ => unreachable!()
+                }
+                binding
+            }
+            Step::Reference(binding, _) => binding
+        };
+        self.append(binding_reference(binding, constant));
+    }
+
+    pub fn tokenize_field<T>(&mut self, name: &'static str, value: &T)
+    where
+        T: ?Sized + ToCustomTokens
+    {
+        self.path.push(Segment::Field(name));
+        self.tokenize_segment(value);
+        self.path.pop();
+    }
+
+    pub fn tokenize_element<T>(&mut self, index: usize, value: &T)
+    where
+        T: ?Sized + ToCustomTokens
+    {
+        self.path.push(Segment::Index(index));
+        self.tokenize_segment(value);
+        self.path.pop();
+    }
+
+    // Elements are profiled together, under `[*]`.
+    fn tokenize_segment<T>(&mut self, value: &T)
+    where
+        T: ?Sized + ToCustomTokens
+    {
+        let segment = match self.profile {
+            Some(ref mut profile) => profile,
+            None => return self.tokenize(value)
+        };
+        segment.enter(match *self.path.last().unwrap() {
+            Segment::Field(name) => format!(".{}", name),
+            Segment::Index(_) => "[*]".to_string()
+        });
+        let start = self.size;
+        self.tokenize(value);
+        let size = self.size_since(start);
+        self.profile.as_mut().unwrap().exit(size);
+    }
+
+    pub fn path(&self) -> String {
+        let mut path = String::new();
+        for segment in &self.path {
+            match *segment {
+                Segment::Field(name) => write!(path, ".{}", name),
+                Segment::Index(index) => write!(path, "[{}]", index)
+            }.unwrap();
+        }
+        path
+    }
+
+    /// Records an error at the current path. Only the first error is kept, and
+    /// tokenization carries on so that callers get as much output as possible.
+    pub fn fail(&mut self, kind: ErrorKind) {
+        if self.error.is_none() {
+            self.error = Some(Error::new(self.path(), kind));
+        }
+    }
+
+    pub fn fail_not_const<T: ?Sized>(&mut self) {
+        self.fail(ErrorKind::NotConst {
+            type_name: type_name::<T>()
+        });
+    }
+
+    /// Side files written so far, for build scripts to declare with
+    /// `cargo:rerun-if-changed` or to clean up.
+    pub fn generated_files(&self) -> &[PathBuf] {
+        &self.generated_files
+    }
+
+    /// Whether side files are actually written, or only named as if they were.
+    pub(crate) fn writes_files(&self) -> bool {
+        self.writes_files
+    }
+
+    pub(crate) fn skip_file_writes(&mut self) {
+        self.writes_files = false;
+    }
+
+    pub(crate) fn add_generated_file(&mut self, file: PathBuf) {
+        if !self.generated_files.contains(&file) {
+            self.generated_files.push(file);
+        }
+    }
+
+    /// Emits the item written by `f` under a fresh `part_N` name, and returns
+    /// that name. Inside `collect_parts` the item is set aside, otherwise it is
+    /// written in place, so callers must be in a position where items are allowed.
+    pub(crate) fn append_part<F>(&mut self, f: F) -> String
+    where
+        F: FnOnce(&mut Self, &str)
+    {
+        let name = format!("part_{}", self.next_part);
+        self.next_part += 1;
+        if self.parts.is_some() {
+            let part = self.with_buffer(|ctx| f(ctx, &name));
+            self.parts.as_mut().unwrap().push(part);
+        } else {
+            f(self, &name);
+        }
+        name
+    }
+
+    /// Runs `f`, returning the items it emitted with `append_part`.
+    pub(crate) fn collect_parts<F>(&mut self, f: F) -> Vec<Tokens>
+    where
+        F: FnOnce(&mut Self)
+    {
+        let outer = self.parts.replace(vec![]);
+        f(self);
+        mem::replace(&mut self.parts, outer).unwrap()
+    }
+
+    /// Runs `f` against an empty token buffer, returning what it emitted.
+    pub(crate) fn with_buffer<F>(&mut self, f: F) -> Tokens
+    where
+        F: FnOnce(&mut Self)
+    {
+        self.buffers.push(Tokens::new());
+        f(self);
+        self.buffers.pop().unwrap()
+    }
+
+    // Recording always happens inside `with_buffer`.
+    fn buffer(&self) -> &str {
+        self.buffers.last().unwrap().as_str()
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            error: self.error.clone(),
+            size: self.size,
+            next_part: self.next_part,
+            parts_len: self.parts.as_ref().map(|parts| parts.len())
+        }
+    }
+
+    /// Forgets the errors, output size and parts since `checkpoint`.
+    fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.error = checkpoint.error;
+        self.size = checkpoint.size;
+        self.next_part = checkpoint.next_part;
+        if let (Some(parts), Some(len)) = (self.parts.as_mut(), checkpoint.parts_len) {
+            parts.truncate(len);
+        }
+    }
+
+    /// Runs `f` against an empty token buffer in `constant` mode. If a value
+    /// cannot be constant, runs it again outside of `constant` mode instead of
+    /// reporting it. Returns what it emitted, and whether that is constant.
+    pub(crate) fn with_constant_buffer<F>(&mut self, f: F) -> (Tokens, bool)
+    where
+        F: Fn(&mut Self)
+    {
+        let checkpoint = self.checkpoint();
+        let constant = mem::replace(&mut self.options.constant, true);
+        let tokens = self.with_buffer(&f);
+        let is_const = match self.error {
+            Some(ref error) if checkpoint.error.is_none() => !matches!(*error.kind(), ErrorKind::NotConst { .. }),
+            _ => true
+        };
+        if is_const {
+            self.options.constant = constant;
+            return (tokens, true);
+        }
+        self.rewind(checkpoint);
+        self.options.constant = false;
+        let tokens = self.with_buffer(&f);
+        self.options.constant = constant;
+        (tokens, false)
+    }
+
+    /// Runs `f` as a dry run that records every subvalue it tokenizes, leaving
+    /// the output, errors and parts as they were.
+    pub(crate) fn record_dedup<F>(&mut self, f: F) -> Recording
+    where
+        F: FnOnce(&mut Self)
+    {
+        let checkpoint = self.checkpoint();
+        let chunk_size = self.options.chunk_size.take();
+        self.dedup = Some(Dedup::Recording(Recording::default()));
+        self.with_buffer(f);
+        self.options.chunk_size = chunk_size;
+        self.rewind(checkpoint);
+        match self.dedup.take() {
+            Some(Dedup::Recording(recording)) => recording,
+            _ => unreachable!()
+        }
+    }
+
+    /// Runs `f` following `plan`, returning the hoisted bindings and the output.
+    /// Neither this nor the recording splits `Vec`s into `fn part_N` items,
+    /// which could not refer to the `let` bindings around them.
+    pub(crate) fn emit_dedup<F>(&mut self, plan: Plan, f: F) -> (Vec<Tokens>, Tokens)
+    where
+        F: FnOnce(&mut Self)
+    {
+        let chunk_size = self.options.chunk_size.take();
+        self.dedup = Some(Dedup::Emitting(Emitting::new(plan)));
+        let body = self.with_buffer(f);
+        self.options.chunk_size = chunk_size;
+        match self.dedup.take() {
+            Some(Dedup::Emitting(emitting)) => (emitting.into_definitions(), body),
+            _ => unreachable!()
+        }
+    }
+
+    /// What a `Context` on another thread needs to carry on from here. Not
+    /// available while deduplicating or collecting parts, whose state cannot be
+    /// split, when chunking, since part names are numbered in order, or with a
+    /// budget, a profile or a trace, which have to follow the output in order.
+    #[cfg(feature = "parallel")]
+    pub(crate) fn fork(&self) -> Option<Fork> {
+        let is_ordered = self.profile.is_some() || self.tracer.is_some() || !self.options.budget.is_unlimited();
+        if self.dedup.is_some() || self.parts.is_some() || self.options.chunk_size.is_some() || is_ordered {
+            return None;
+        }
+        Some(Fork {
+            options: self.options.clone(),
+            path: self.path.clone(),
+            writes_files: self.writes_files
+        })
+    }
+
+    /// Appends the output of a forked `Context`, keeping the first error.
+    #[cfg(feature = "parallel")]
+    pub(crate) fn join(&mut self, joined: Joined) {
+        if self.size.tokens > 0 && joined.size.tokens > 0 {
+            self.size.bytes += 1;
+        }
+        self.size.bytes += joined.size.bytes;
+        self.size.tokens += joined.size.tokens;
+        self.size.depth = self.size.depth.max(joined.size.depth);
+        self.append_buffered(joined.tokens);
+        if self.error.is_none() {
+            self.error = joined.error;
+        }
+        joined
+            .generated_files
+            .into_iter()
+            .for_each(|file| self.add_generated_file(file));
+    }
+
+    /// Runs `f`, reporting the size of what it emitted by field and by type.
+    pub(crate) fn profile<F>(&mut self, f: F) -> SizeReport
+    where
+        F: FnOnce(&mut Self)
+    {
+        let start = self.size;
+        self.profile = Some(Profile::new());
+        f(self);
+        let size = self.size_since(start);
+        self.profile.take().unwrap().into_report(size)
+    }
+
+    /// Runs `f`, recording which type and impl emitted each value.
+    pub(crate) fn trace<F>(&mut self, f: F) -> TraceNode
+    where
+        F: FnOnce(&mut Self)
+    {
+        self.tracer = Some(Tracer::default());
+        f(self);
+        self.tracer.take().unwrap().into_root()
+    }
+
+    /// Notes the strategy picked for the value being tokenized, for traces.
+    pub(crate) fn trace_note(&mut self, note: &'static str) {
+        if let Some(ref mut tracer) = self.tracer {
+            tracer.note(note);
+        }
+    }
+
+    pub fn finish(self) -> Result<(), Error> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(())
+        }
+    }
+}
+
+/// Lets impls that only provide `to_custom_tokens` write into a `Context`.
+impl<'a> TokenSink for Context<'a> {
+    fn append_token(&mut self, token: &str) {
+        self.append(token);
+    }
+}
+
+/// State to go back to after a dry run. See `Context::rewind`.
+struct Checkpoint {
+    error: Option<Error>,
+    size: OutputSize,
+    next_part: usize,
+    parts_len: Option<usize>
+}
+
+#[cfg(feature = "parallel")]
+pub(crate) struct Fork {
+    options: Options,
+    path: Vec<Segment>,
+    writes_files: bool
+}
+
+#[cfg(feature = "parallel")]
+pub(crate) struct Joined {
+    tokens: Tokens,
+    size: OutputSize,
+    error: Option<Error>,
+    generated_files: Vec<PathBuf>
+}
+
+#[cfg(feature = "parallel")]
+impl Fork {
+    /// Runs `f` against a fresh `Context` that starts at the forked path.
+    pub fn run<F>(&self, f: F) -> Joined
+    where
+        F: FnOnce(&mut Context)
+    {
+        let mut tokens = Tokens::new();
+        let (size, error, generated_files) = {
+            let mut ctx = Context::with_options(&mut tokens, self.options.clone());
+            ctx.path = self.path.clone();
+            ctx.writes_files = self.writes_files;
+            f(&mut ctx);
+            (ctx.size, ctx.error, ctx.generated_files)
+        };
+        Joined {
+            tokens,
+            size,
+            error,
+            generated_files
+        }
+    }
+}
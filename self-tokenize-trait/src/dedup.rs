@@ -0,0 +1,357 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::any::type_name;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use quote::Tokens;
+
+use context::Context;
+use ToCustomTokens;
+
+/// Subvalues whose tokens are shorter than this are never worth a binding.
+/// Longer ones are hoisted only when that makes the output shorter.
+pub const MIN_DEDUP_LEN: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DedupReport {
+    /// Length of the tokens without deduplication.
+    pub original_size: usize,
+    /// Length of the emitted tokens, including the bindings.
+    pub deduplicated_size: usize,
+    /// Number of hoisted subvalues.
+    pub bindings: usize
+}
+
+impl fmt::Display for DedupReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let saved = self.original_size as f64 - self.deduplicated_size as f64;
+        let percent = if self.original_size == 0 {
+            0.0
+        } else {
+            saved * 100.0 / self.original_size as f64
+        };
+        write!(
+            f,
+            "{} subvalues hoisted, {} -> {} bytes ({:.1}% smaller)",
+            self.bindings, self.original_size, self.deduplicated_size, percent
+        )
+    }
+}
+
+/// Emits `value` as a block that binds each structurally identical `Clone`
+/// subvalue once, with `let` (or `const` in `constant` mode), and refers to it
+/// from every place it occurs. `chunk_size` is ignored inside, since the
+/// `fn part_N` helpers could not see the bindings.
+pub fn append_deduplicated<T>(ctx: &mut Context, value: &T) -> DedupReport
+where
+    T: ?Sized + ToCustomTokens
+{
+    let recording = ctx.record_dedup(|ctx| ctx.tokenize(value));
+    let original_size = recording.size;
+    let plan = recording.into_plan(ctx.options().constant);
+    let bindings = plan.bindings;
+
+    let (definitions, body) = ctx.emit_dedup(plan, |ctx| ctx.tokenize(value));
+    let mut deduplicated_size = body.as_str().len();
+    if definitions.is_empty() {
+        ctx.append_buffered(body);
+    } else {
+        // Braces and the spaces around each definition and the body.
+        deduplicated_size += 4 + definitions.iter().map(|definition| definition.as_str().len() + 1).sum::<usize>();
+        ctx.append("{");
+        definitions.into_iter().for_each(|definition| ctx.append_buffered(definition));
+        ctx.append_buffered(body);
+        ctx.append("}");
+    }
+
+    DedupReport {
+        original_size,
+        deduplicated_size,
+        bindings
+    }
+}
+
+trait IsClone {
+    fn is_clone() -> bool;
+}
+
+impl<T: ?Sized> IsClone for T {
+    default fn is_clone() -> bool {
+        false
+    }
+}
+
+impl<T: Clone> IsClone for T {
+    fn is_clone() -> bool {
+        true
+    }
+}
+
+/// Types whose clones share their contents with the original. Hoisting a
+/// subvalue that contains one would make its occurrences share it too, where
+/// the original value had separate allocations.
+trait IsShared {
+    fn is_shared() -> bool;
+}
+
+impl<T: ?Sized> IsShared for T {
+    default fn is_shared() -> bool {
+        false
+    }
+}
+
+impl<T: ?Sized> IsShared for Rc<T> {
+    fn is_shared() -> bool {
+        true
+    }
+}
+
+impl<T: ?Sized> IsShared for Arc<T> {
+    fn is_shared() -> bool {
+        true
+    }
+}
+
+impl<'a, T: ?Sized + ToOwned> IsShared for Cow<'a, T> {
+    fn is_shared() -> bool {
+        true
+    }
+}
+
+pub enum Dedup {
+    Recording(Recording),
+    Emitting(Emitting)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Visit {
+    key: Option<usize>,
+    // Number of visits in this subtree, including itself.
+    len: usize,
+    // Number of shared values visited before this subtree ended.
+    shared: usize
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    // Length of the subvalue's tokens.
+    len: usize,
+    // Length of its binding's definition, besides the subvalue itself.
+    overhead: usize,
+    has_inferred_type: bool
+}
+
+impl Candidate {
+    /// Whether binding a subvalue that occurs `count` times makes the output
+    /// shorter, given that every occurrence becomes a reference.
+    fn saves(&self, count: usize, constant: bool) -> bool {
+        let reference_len = binding_reference(0, constant).len();
+        count >= 2 && (count - 1) * self.len > self.overhead + count * reference_len
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Recording {
+    keys: HashMap<(&'static str, String), usize>,
+    candidates: Vec<Candidate>,
+    visits: Vec<Visit>,
+    shared: usize,
+    size: usize
+}
+
+impl Recording {
+    pub fn begin(&mut self) -> usize {
+        self.visits.push(Visit {
+            key: None,
+            len: 0,
+            shared: self.shared
+        });
+        self.visits.len() - 1
+    }
+
+    pub fn end<T: ?Sized>(&mut self, visit: usize, text: &str, type_text: &str) {
+        if T::is_shared() {
+            self.shared += 1;
+        }
+        let has_shared = self.shared > self.visits[visit].shared;
+        let key = if T::is_clone() && !has_shared && text.len() >= MIN_DEDUP_LEN {
+            let next = self.keys.len();
+            let key = *self.keys
+                .entry((type_name::<T>(), text.to_string()))
+                .or_insert(next);
+            if key == next {
+                self.candidates.push(Candidate {
+                    len: text.len(),
+                    // `let dedup_0 : T = ;`, give or take the digits.
+                    overhead: type_text.len() + 20,
+                    has_inferred_type: type_text.split_whitespace().any(|token| token == "_")
+                });
+            }
+            Some(key)
+        } else {
+            None
+        };
+        self.visits[visit] = Visit {
+            key,
+            len: self.visits.len() - visit,
+            shared: self.shared
+        };
+        if visit == 0 {
+            self.size = text.len();
+        }
+    }
+
+    fn into_plan(self, constant: bool) -> Plan {
+        let mut counts = vec![0; self.keys.len()];
+        self.visits
+            .iter()
+            .filter_map(|visit| visit.key)
+            .for_each(|key| counts[key] += 1);
+        // Constants need their type spelled out in full.
+        let mut hoisted: Vec<bool> = counts
+            .iter()
+            .zip(&self.candidates)
+            .map(|(&count, candidate)| candidate.saves(count, constant) && !(constant && candidate.has_inferred_type))
+            .collect();
+
+        // Occurrences inside a hoisted subvalue are only emitted once, as part of
+        // its binding, so keep only the keys that still repeat.
+        loop {
+            let mut counts = vec![0; hoisted.len()];
+            let mut index = 0;
+            while index < self.visits.len() {
+                let visit = self.visits[index];
+                match visit.key {
+                    Some(key) if hoisted[key] => {
+                        counts[key] += 1;
+                        index += if counts[key] > 1 { visit.len } else { 1 };
+                    }
+                    Some(key) => {
+                        counts[key] += 1;
+                        index += 1;
+                    }
+                    None => index += 1
+                }
+            }
+            let next: Vec<bool> = hoisted
+                .iter()
+                .zip(counts.iter().zip(&self.candidates))
+                .map(|(&hoisted, (&count, candidate))| hoisted && candidate.saves(count, constant))
+                .collect();
+            if next == hoisted {
+                break;
+            }
+            hoisted = next;
+        }
+
+        let mut binding_of = vec![None; hoisted.len()];
+        let mut bindings = 0;
+        let steps = self.visits
+            .iter()
+            .map(|visit| match visit.key {
+                Some(key) if hoisted[key] => match binding_of[key] {
+                    Some(binding) => Step::Reference(binding, visit.len),
+                    None => {
+                        binding_of[key] = Some(bindings);
+                        bindings += 1;
+                        Step::Define(bindings - 1)
+                    }
+                },
+                _ => Step::Plain
+            })
+            .collect();
+
+        Plan {
+            steps,
+            bindings,
+            constant
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Step {
+    Plain,
+    Define(usize),
+    // Binding, and the number of visits to skip, since only the first
+    // occurrence of a subvalue is tokenized.
+    Reference(usize, usize)
+}
+
+#[derive(Debug)]
+pub struct Plan {
+    steps: Vec<Step>,
+    bindings: usize,
+    constant: bool
+}
+
+#[derive(Debug)]
+pub struct Emitting {
+    plan: Plan,
+    visit: usize,
+    definitions: Vec<Tokens>
+}
+
+impl Emitting {
+    pub fn new(plan: Plan) -> Self {
+        Emitting {
+            plan,
+            visit: 0,
+            definitions: vec![]
+        }
+    }
+
+    pub fn next_step(&mut self) -> Step {
+        let step = self.plan.steps[self.visit];
+        self.visit += match step {
+            Step::Reference(_, len) => len,
+            _ => 1
+        };
+        step
+    }
+
+    pub fn is_constant(&self) -> bool {
+        self.plan.constant
+    }
+
+    pub fn define(&mut self, definition: Tokens) {
+        self.definitions.push(definition);
+    }
+
+    pub fn into_definitions(self) -> Vec<Tokens> {
+        self.definitions
+    }
+}
+
+/// The tokens `Context` replaces each occurrence of a hoisted subvalue with:
+/// the binding itself for constants, otherwise a clone of it. Unlike
+/// `.clone()`, the path resolves without the prelude and cannot pick up an
+/// inherent or imported method of the same name.
+pub fn binding_reference(binding: usize, constant: bool) -> String {
+    if constant {
+        binding_name(binding, constant)
+    } else {
+        format!("::std::clone::Clone :: clone ( & {} )", binding_name(binding, constant))
+    }
+}
+
+pub fn binding_name(binding: usize, constant: bool) -> String {
+    if constant {
+        format!("DEDUP_{}", binding)
+    } else {
+        format!("dedup_{}", binding)
+    }
+}
@@ -0,0 +1,69 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::error;
+use std::fmt;
+use std::path::PathBuf;
+
+use budget::Limit;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    NotConst { type_name: &'static str },
+    Io { file: PathBuf, message: String },
+    BudgetExceeded { limit: Limit },
+    /// The type is needed in the output, but `ToCustomTokens::to_type_tokens`
+    /// leaves part of it to inference, e.g. for types without the derive.
+    UnnamedType { type_name: &'static str },
+    /// A `static` needs `Send + Sync`. See `append_thread_local`.
+    NotThreadSafe { type_name: &'static str }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    path: String,
+    kind: ErrorKind
+}
+
+impl Error {
+    pub fn new(path: String, kind: ErrorKind) -> Self {
+        Error { path, kind }
+    }
+
+    /// Path of the offending field from the tokenized value, e.g. `.baz[1].unit`.
+    /// Empty when the value itself is at fault.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let subject = if self.path.is_empty() {
+            "value".to_string()
+        } else {
+            format!("field `{}`", self.path)
+        };
+        match self.kind {
+            ErrorKind::NotConst { type_name } => write!(f, "{} of type `{}` cannot be constructed in a const context", subject, type_name),
+            ErrorKind::Io { ref file, ref message } => write!(f, "{} could not be written to `{}`: {}", subject, file.display(), message),
+            ErrorKind::BudgetExceeded { limit } => write!(f, "{} exceeds the budget of {}", subject, limit),
+            ErrorKind::UnnamedType { type_name } => write!(f, "{} of type `{}` has no name to emit it with", subject, type_name),
+            ErrorKind::NotThreadSafe { type_name } => write!(f, "{} of type `{}` cannot be shared between threads", subject, type_name)
+        }
+    }
+}
+
+impl error::Error for Error {}
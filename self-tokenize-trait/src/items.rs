@@ -0,0 +1,205 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::any::type_name;
+
+use context::Context;
+use error::ErrorKind;
+use ToCustomTokens;
+
+trait ThreadSafety {
+    fn is_thread_safe() -> bool;
+}
+
+impl<T: ?Sized> ThreadSafety for T {
+    default fn is_thread_safe() -> bool {
+        false
+    }
+}
+
+impl<T: ?Sized + Send + Sync> ThreadSafety for T {
+    fn is_thread_safe() -> bool {
+        true
+    }
+}
+
+/// Emits a `static` item named `name` that is initialized with `value` on first
+/// access, using `LazyLock`. Only a target older than Rust 1.80 gets
+/// `::once_cell::sync::Lazy` instead, which the crate compiling the output has to
+/// depend on. Values that are not `Send + Sync` (e.g. `Rc`) are reported, see
+/// `append_thread_local`.
+pub fn append_lazy_static<T>(ctx: &mut Context, vis: &str, name: &str, value: &T)
+where
+    T: ToCustomTokens
+{
+    if !T::is_thread_safe() {
+        ctx.fail(ErrorKind::NotThreadSafe {
+            type_name: type_name::<T>()
+        });
+    }
+    let lazy = if ctx.options().supports(1, 80) {
+        "::std::sync::LazyLock"
+    } else {
+        "::once_cell::sync::Lazy"
+    };
+    append_visibility(ctx, vis);
+    ctx.append("static");
+    ctx.append(name);
+    ctx.append(":");
+    ctx.append(lazy);
+    ctx.append("<");
+    ctx.append_item_type::<T>();
+    ctx.append(">");
+    ctx.append("=");
+    ctx.append(lazy);
+    ctx.append("::");
+    ctx.append("new");
+    ctx.append("(");
+    ctx.append("||");
+    ctx.tokenize(value);
+    ctx.append(")");
+    ctx.append(";");
+}
+
+/// Emits a `thread_local!` static named `name`, for values that are not
+/// `Send + Sync`. Each thread initializes its own copy on first access.
+pub fn append_thread_local<T>(ctx: &mut Context, vis: &str, name: &str, value: &T)
+where
+    T: ToCustomTokens
+{
+    ctx.append_prelude_path("thread_local!", "::std::thread_local!");
+    ctx.append("{");
+    append_visibility(ctx, vis);
+    ctx.append("static");
+    ctx.append(name);
+    ctx.append(":");
+    ctx.append_item_type::<T>();
+    ctx.append("=");
+    ctx.tokenize(value);
+    ctx.append(";");
+    ctx.append("}");
+}
+
+/// Emits `mod name { .. }` with a `pub(super) fn value()` returning `value`.
+/// With `Options::chunk_size` set, large `Vec`s are split into `part_N`
+/// functions placed next to it in the module.
+pub fn append_chunked_module<T>(ctx: &mut Context, vis: &str, name: &str, value: &T)
+where
+    T: ToCustomTokens
+{
+    append_visibility(ctx, vis);
+    ctx.append("mod");
+    ctx.append(name);
+    ctx.append("{");
+    ctx.append("use");
+    ctx.append("super");
+    ctx.append("::");
+    ctx.append("*");
+    ctx.append(";");
+    let parts = ctx.collect_parts(|ctx| {
+        ctx.append("pub");
+        ctx.append("(");
+        ctx.append("super");
+        ctx.append(")");
+        ctx.append("fn");
+        ctx.append("value");
+        ctx.append("(");
+        ctx.append(")");
+        ctx.append("->");
+        ctx.append_item_type::<T>();
+        ctx.append("{");
+        ctx.tokenize(value);
+        ctx.append("}");
+    });
+    parts.into_iter().for_each(|part| ctx.append_buffered(part));
+    ctx.append("}");
+}
+
+/// Emits `impl Default for T` returning `value`. Like the items below, it names
+/// `T` with `ToCustomTokens::to_type_tokens`, which the derive implements, and
+/// reports `ErrorKind::UnnamedType` for types that cannot be named.
+pub fn append_default_impl<T>(ctx: &mut Context, value: &T)
+where
+    T: ToCustomTokens
+{
+    ctx.append("impl");
+    ctx.append_prelude_path("Default", "::std::default::Default");
+    ctx.append("for");
+    ctx.append_item_type::<T>();
+    ctx.append("{");
+    ctx.append("fn");
+    ctx.append("default");
+    ctx.append("(");
+    ctx.append(")");
+    ctx.append("->");
+    ctx.append("Self");
+    ctx.append("{");
+    ctx.tokenize(value);
+    ctx.append("}");
+    ctx.append("}");
+}
+
+/// Emits `fn name() -> T` returning `value`, e.g. `pub fn preset_dark() -> Theme`.
+/// With `Options::constant` set, it is a `const fn` if `value` can be constant,
+/// and a plain `fn` otherwise.
+pub fn append_constructor<T>(ctx: &mut Context, vis: &str, name: &str, value: &T)
+where
+    T: ToCustomTokens
+{
+    // Tokenized up front, to find out whether it is a `const fn`.
+    let constant_body = if ctx.options().constant {
+        Some(ctx.with_constant_buffer(|ctx| ctx.tokenize(value)))
+    } else {
+        None
+    };
+    append_visibility(ctx, vis);
+    if let Some((_, true)) = constant_body {
+        ctx.append("const");
+    }
+    ctx.append("fn");
+    ctx.append(name);
+    ctx.append("(");
+    ctx.append(")");
+    ctx.append("->");
+    ctx.append_item_type::<T>();
+    ctx.append("{");
+    match constant_body {
+        Some((body, _)) => ctx.append_buffered(body),
+        None => ctx.tokenize(value)
+    }
+    ctx.append("}");
+}
+
+/// Emits `impl T { const NAME: Self = value; }`. Set `Options::constant` to
+/// have values that cannot be constants reported by `Context::finish`.
+pub fn append_associated_const<T>(ctx: &mut Context, vis: &str, name: &str, value: &T)
+where
+    T: ToCustomTokens
+{
+    ctx.append("impl");
+    ctx.append_item_type::<T>();
+    ctx.append("{");
+    append_visibility(ctx, vis);
+    ctx.append("const");
+    ctx.append(name);
+    ctx.append(":");
+    ctx.append("Self");
+    ctx.append("=");
+    ctx.tokenize(value);
+    ctx.append(";");
+    ctx.append("}");
+}
+
+fn append_visibility(ctx: &mut Context, vis: &str) {
+    if !vis.is_empty() {
+        ctx.append(vis);
+    }
+}
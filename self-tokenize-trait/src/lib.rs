@@ -12,14 +12,24 @@ specific language governing permissions and limitations under the License.
 #![cfg_attr(feature = "cargo-clippy", allow(implicit_hasher, match_ref_pats))]
 #![feature(specialization)]
 
+#[cfg(feature = "compress")]
+extern crate miniz_oxide;
 extern crate num_traits;
 extern crate ordered_float;
+#[cfg(feature = "proc-macro2")]
+extern crate proc_macro2;
+#[cfg(feature = "parallel")]
+extern crate rayon;
 extern crate quote;
 extern crate smallvec;
+#[cfg(feature = "stacker")]
+extern crate stacker;
 
 use std::borrow::Cow;
 use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Debug, Write};
 use std::hash::Hash;
+use std::iter;
 use std::rc::Rc;
 use std::sync::Arc;
 
@@ -27,17 +37,128 @@ use num_traits::Float;
 use ordered_float::OrderedFloat;
 use smallvec::{Array, SmallVec};
 
+mod budget;
+mod bytes;
+mod chunks;
+mod compact;
+#[cfg(feature = "compress")]
+mod compressed;
+mod context;
+mod dedup;
+mod error;
+mod items;
+mod mirror;
+mod parallel;
+mod pretty;
+mod report;
+mod runs;
+mod sink;
+mod source_file;
+mod source_map;
+mod trace;
+
+pub use budget::{estimate_size, Budget, Limit, OutputSize};
+#[cfg(feature = "compress")]
+pub use compressed::{Compressed, Payload};
+pub use context::{Context, Edition, Options, RustVersion, SideFiles};
+pub use dedup::{append_deduplicated, DedupReport};
+pub use error::{Error, ErrorKind};
+pub use items::{append_associated_const, append_chunked_module, append_constructor, append_default_impl, append_lazy_static,
+                append_thread_local};
+pub use mirror::StaticMirror;
+pub use pretty::{pretty_print, PrettyOptions};
+pub use report::{size_report, SizeNode, SizeReport, TypeSize};
+#[cfg(feature = "proc-macro2")]
+pub use sink::TokenStreamSink;
+pub use sink::{TokenSink, WriteSink};
+pub use source_file::{ItemKind, SourceFile};
+pub use source_map::{source_map, SourceMap, SourceMapEntry};
+pub use trace::{trace, Trace, TraceNode};
+
 pub use quote::{ToTokens, Tokens};
 
+use bytes::append_byte_string;
+use chunks::append_chunked_vec;
+use compact::Compact;
+use parallel::append_elements;
+use runs::{append_repeat_array, append_repeat_vec, RepeatArray, RepeatVec};
+
+/// Impls provide `to_custom_tokens_with`, which emits through a `Context` and
+/// sees its `Options`. The other methods are entry points built on top of it.
 pub trait ToCustomTokens {
-    fn to_custom_tokens(&self, &mut Tokens);
+    fn to_custom_tokens(&self, tokens: &mut dyn TokenSink) {
+        self.to_custom_tokens_with(&mut Context::new(tokens));
+    }
+
+    /// Like `to_custom_tokens`, but reports values that cannot be emitted as
+    /// requested by `options`, e.g. heap allocations in `constant` mode.
+    fn try_to_custom_tokens(&self, tokens: &mut dyn TokenSink, options: Options) -> Result<(), Error> {
+        let mut ctx = Context::with_options(tokens, options);
+        self.to_custom_tokens_with(&mut ctx);
+        ctx.finish()
+    }
+
+    fn to_custom_tokens_with(&self, ctx: &mut Context);
+
+    fn to_type_tokens(ctx: &mut Context) {
+        ctx.append("_");
+    }
+
+    /// Names the impl in traces, e.g. `numeric_slice_impls!` or
+    /// `#[derive(SelfTokenize)]`.
+    fn impl_name() -> &'static str {
+        "impl ToCustomTokens"
+    }
+
+    /// Whether sequences of `Self` are built on the heap one element at a time
+    /// instead of from an array literal, which debug builds place on the stack
+    /// first. Enabled by `#[self_tokenize(heap)]`.
+    fn is_heap_direct() -> bool {
+        false
+    }
 }
 
 macro_rules! default_impls {
     ( $( $ty:ty )+ ) => { $(
         impl ToCustomTokens for $ty where Self: ToTokens {
-            fn to_custom_tokens(&self, tokens: &mut Tokens) {
-                self.to_tokens(tokens);
+            fn to_custom_tokens_with(&self, ctx: &mut Context) {
+                let mut literal = Tokens::new();
+                self.to_tokens(&mut literal);
+                ctx.append(literal);
+            }
+
+            fn to_type_tokens(ctx: &mut Context) {
+                ctx.append(stringify!($ty));
+            }
+
+            fn impl_name() -> &'static str {
+                "default_impls!"
+            }
+        }
+    )+ };
+}
+
+macro_rules! numeric_impls {
+    ( $( $ty:ty )+ ) => { $(
+        impl ToCustomTokens for $ty {
+            fn to_custom_tokens_with(&self, ctx: &mut Context) {
+                let mut literal = Tokens::new();
+                self.to_tokens(&mut literal);
+                if literal.as_str().starts_with('-') {
+                    ctx.append("(");
+                    ctx.append(literal);
+                    ctx.append(")");
+                } else {
+                    ctx.append(literal);
+                }
+            }
+
+            fn to_type_tokens(ctx: &mut Context) {
+                ctx.append(stringify!($ty));
+            }
+
+            fn impl_name() -> &'static str {
+                "numeric_impls!"
             }
         }
     )+ };
@@ -46,8 +167,42 @@ macro_rules! default_impls {
 macro_rules! slice_impls {
     ( $( $ty:ty )+ ) => { $(
         impl ToCustomTokens for [$ty] {
-            fn to_custom_tokens(&self, tokens: &mut Tokens) {
-                tokens.append(format!("{:?}", self));
+            fn to_custom_tokens_with(&self, ctx: &mut Context) {
+                if ctx.options().typed && self.is_empty() {
+                    append_empty_array::<$ty>(ctx);
+                } else if self.is_repeat_array() {
+                    append_repeat_array(ctx, self);
+                } else {
+                    append_list(ctx, self, "");
+                }
+            }
+
+            fn impl_name() -> &'static str {
+                "slice_impls!"
+            }
+        }
+    )+ }
+}
+
+macro_rules! numeric_slice_impls {
+    ( $( $ty:ty )+ ) => { $(
+        impl ToCustomTokens for [$ty] {
+            fn to_custom_tokens_with(&self, ctx: &mut Context) {
+                if self.is_repeat_array() {
+                    append_repeat_array(ctx, self);
+                } else if self.is_compact(ctx.options()) {
+                    self.append_compact(ctx, false);
+                } else if !ctx.options().typed {
+                    append_list(ctx, self, "");
+                } else if self.is_empty() {
+                    append_empty_array::<$ty>(ctx);
+                } else {
+                    append_list(ctx, self, stringify!($ty));
+                }
+            }
+
+            fn impl_name() -> &'static str {
+                "numeric_slice_impls!"
             }
         }
     )+ }
@@ -59,8 +214,20 @@ macro_rules! array_impls {
         where
             T: ToCustomTokens
         {
-            fn to_custom_tokens(&self, tokens: &mut Tokens) {
-                self[..].to_custom_tokens(tokens);
+            fn to_custom_tokens_with(&self, ctx: &mut Context) {
+                ctx.tokenize(&self[..]);
+            }
+
+            fn to_type_tokens(ctx: &mut Context) {
+                ctx.append("[");
+                ctx.append_type::<$T>();
+                ctx.append(";");
+                ctx.append(stringify!($N));
+                ctx.append("]");
+            }
+
+            fn impl_name() -> &'static str {
+                "array_impls!"
             }
         }
     )+ }
@@ -72,24 +239,101 @@ macro_rules! tuple_impls {
         where
             $( $T: ToCustomTokens ),*
         {
-            fn to_custom_tokens(&self, tokens: &mut Tokens) {
-                tokens.append("(");
+            fn to_custom_tokens_with(&self, ctx: &mut Context) {
+                ctx.append("(");
                 $(
-                    self.$idx.to_custom_tokens(tokens);
-                    tokens.append(",");
+                    ctx.tokenize_field(stringify!($idx), &self.$idx);
+                    ctx.append(",");
                 )*
-                tokens.append(")");
+                ctx.append(")");
+            }
+
+            fn to_type_tokens(ctx: &mut Context) {
+                ctx.append("(");
+                $(
+                    ctx.append_type::<$T>();
+                    ctx.append(",");
+                )*
+                ctx.append(")");
+            }
+
+            fn impl_name() -> &'static str {
+                "tuple_impls!"
             }
         }
     )+ }
 }
 
+// Elements formatted at a time by `append_list`.
+const LIST_PART_LEN: usize = 4096;
+
+// Emits `[a, b, c]` with each element followed by `suffix` as a single token,
+// formatted in parts so that long slices stream to the sink.
+fn append_list<T: Debug>(ctx: &mut Context, items: &[T], suffix: &str) {
+    let parts = items.chunks(LIST_PART_LEN).enumerate().map(|(index, chunk)| {
+        let mut part = String::new();
+        for (offset, item) in chunk.iter().enumerate() {
+            if index + offset > 0 {
+                part.push_str(", ");
+            }
+            write!(part, "{:?}{}", item, suffix).unwrap();
+        }
+        part
+    });
+    ctx.append_parts(iter::once("[".to_string()).chain(parts).chain(iter::once("]".to_string())));
+}
+
+fn append_empty_array<T>(ctx: &mut Context)
+where
+    T: ?Sized + ToCustomTokens
+{
+    if ctx.options().constant {
+        ctx.append("{");
+        ctx.append("const");
+        ctx.append("EMPTY");
+        ctx.append(":");
+        ctx.append("[");
+        ctx.append_type::<T>();
+        ctx.append(";");
+        ctx.append("0");
+        ctx.append("]");
+        ctx.append("=");
+        ctx.append("[");
+        ctx.append("]");
+        ctx.append(";");
+        ctx.append("EMPTY");
+        ctx.append("}");
+        return;
+    }
+    ctx.append("<");
+    ctx.append("[");
+    ctx.append_type::<T>();
+    ctx.append(";");
+    ctx.append("0");
+    ctx.append("]");
+    ctx.append("as");
+    ctx.append("::std::default::Default");
+    ctx.append(">");
+    ctx.append("::");
+    ctx.append("default");
+    ctx.append("(");
+    ctx.append(")");
+}
+
 default_impls!(
-    bool i8 u8 i16 u16 i32 u32 i64 u64 f32 f64 isize usize char str
+    bool char str
+);
+
+numeric_impls!(
+    i8 u8 i16 u16 i32 u32 i64 u64 f32 f64 isize usize
 );
 
 slice_impls!(
-    bool i8 u8 i16 u16 i32 u32 i64 u64 isize usize char
+    bool char
+);
+
+numeric_slice_impls!(
+    i8 i16 u16 i32 u32 i64 u64 isize usize
 );
 
 array_impls! {
@@ -121,8 +365,52 @@ impl<'a, T> ToCustomTokens for &'a T
 where
     T: ?Sized + ToCustomTokens
 {
-    fn to_custom_tokens(&self, tokens: &mut Tokens) {
-        (*self).to_custom_tokens(tokens);
+    fn to_custom_tokens_with(&self, ctx: &mut Context) {
+        if ctx.options().constant {
+            (*self).to_ref_tokens(ctx);
+        } else {
+            ctx.tokenize(*self);
+        }
+    }
+
+    fn to_type_tokens(ctx: &mut Context) {
+        ctx.append("&");
+        ctx.append_type::<T>();
+    }
+
+    fn impl_name() -> &'static str {
+        "impl for &'a T"
+    }
+}
+
+trait ToRefTokens {
+    fn to_ref_tokens(&self, ctx: &mut Context);
+}
+
+impl<T> ToRefTokens for T
+where
+    T: ?Sized + ToCustomTokens
+{
+    default fn to_ref_tokens(&self, ctx: &mut Context) {
+        ctx.append("&");
+        ctx.tokenize(self);
+    }
+}
+
+impl ToRefTokens for str {
+    fn to_ref_tokens(&self, ctx: &mut Context) {
+        ctx.tokenize(self);
+    }
+}
+
+impl ToRefTokens for [u8] {
+    fn to_ref_tokens(&self, ctx: &mut Context) {
+        if self.is_empty() {
+            ctx.append("&");
+            ctx.tokenize(self);
+        } else {
+            append_byte_string(ctx, self);
+        }
     }
 }
 
@@ -130,12 +418,59 @@ impl ToCustomTokens for String
 where
     Self: ToTokens
 {
-    fn to_custom_tokens(&self, tokens: &mut Tokens) {
-        self.to_tokens(tokens);
-        tokens.append(".");
-        tokens.append("to_string");
-        tokens.append("(");
-        tokens.append(")");
+    fn to_custom_tokens_with(&self, ctx: &mut Context) {
+        if ctx.options().constant {
+            if self.is_empty() && ctx.options().supports(1, 39) {
+                ctx.append_prelude_path("String", "::std::string::String");
+                ctx.append("::");
+                ctx.append("new");
+                ctx.append("(");
+                ctx.append(")");
+                return;
+            }
+            ctx.fail_not_const::<Self>();
+        }
+        if ctx.options().prefers(1, 56) {
+            if ctx.options().no_implicit_prelude {
+                ctx.append("<");
+                ctx.append("::std::string::String");
+                ctx.append("as");
+                ctx.append("::std::convert::From");
+                ctx.append("<");
+                ctx.append("&");
+                ctx.append("str");
+                ctx.append(">");
+                ctx.append(">");
+            } else {
+                ctx.append("String");
+            }
+            ctx.append("::");
+            ctx.append("from");
+            ctx.append("(");
+            ctx.tokenize(self.as_str());
+            ctx.append(")");
+        } else if ctx.options().no_implicit_prelude {
+            ctx.append("::std::string::ToString");
+            ctx.append("::");
+            ctx.append("to_string");
+            ctx.append("(");
+            ctx.tokenize(self.as_str());
+            ctx.append(")");
+        } else {
+            ctx.tokenize(self.as_str());
+            ctx.append(".");
+            ctx.append("to_string");
+            ctx.append("(");
+            ctx.append(")");
+        }
+    }
+
+    fn to_type_tokens(ctx: &mut Context) {
+        ctx.append_prelude_path("String", "::std::string::String");
+    }
+
+    fn impl_name() -> &'static str {
+        "impl for String"
     }
 }
 
@@ -143,28 +478,80 @@ impl<T> ToCustomTokens for Option<T>
 where
     T: ToCustomTokens
 {
-    fn to_custom_tokens(&self, tokens: &mut Tokens) {
+    fn to_custom_tokens_with(&self, ctx: &mut Context) {
         match self {
             &Some(ref value) => {
-                tokens.append("Some");
-                tokens.append("(");
-                value.to_custom_tokens(tokens);
-                tokens.append(")");
+                ctx.append_prelude_path("Some", "::std::option::Option::Some");
+                ctx.append("(");
+                ctx.tokenize(value);
+                ctx.append(")");
             }
             &None => {
-                tokens.append("None");
+                ctx.append_prelude_path("None", "::std::option::Option::None");
+                if ctx.options().typed {
+                    ctx.append("::");
+                    ctx.append("<");
+                    ctx.append_type::<T>();
+                    ctx.append(">");
+                }
             }
         }
     }
+
+    fn to_type_tokens(ctx: &mut Context) {
+        ctx.append_prelude_path("Option", "::std::option::Option");
+        ctx.append("<");
+        ctx.append_type::<T>();
+        ctx.append(">");
+    }
+
+    fn impl_name() -> &'static str {
+        "impl for Option<T>"
+    }
 }
 
 impl<T> ToCustomTokens for Vec<T>
 where
     T: ToCustomTokens
 {
-    fn to_custom_tokens(&self, tokens: &mut Tokens) {
-        tokens.append("vec!");
-        self[..].to_custom_tokens(tokens);
+    fn to_custom_tokens_with(&self, ctx: &mut Context) {
+        let constant = ctx.options().constant;
+        if constant && !(self.is_empty() && ctx.options().supports(1, 39)) {
+            ctx.fail_not_const::<Self>();
+        }
+        if (constant || ctx.options().typed) && self.is_empty() {
+            ctx.append_prelude_path("Vec", "::std::vec::Vec");
+            if ctx.options().typed {
+                ctx.append("::");
+                ctx.append("<");
+                ctx.append_type::<T>();
+                ctx.append(">");
+            }
+            ctx.append("::");
+            ctx.append("new");
+            ctx.append("(");
+            ctx.append(")");
+        } else if let Some(segments) = self[..].repeat_vec_segments() {
+            append_repeat_vec(ctx, &self[..], &segments);
+        } else if let Some(chunk_size) = ctx.options()
+            .chunk_size
+            .filter(|&chunk_size| chunk_size > 0 && self.len() > chunk_size)
+        {
+            append_chunked_vec(ctx, &self[..], chunk_size);
+        } else {
+            self[..].to_vec_tokens(ctx);
+        }
+    }
+
+    fn to_type_tokens(ctx: &mut Context) {
+        ctx.append_prelude_path("Vec", "::std::vec::Vec");
+        ctx.append("<");
+        ctx.append_type::<T>();
+        ctx.append(">");
+    }
+
+    fn impl_name() -> &'static str {
+        "impl for Vec<T>"
     }
 }
 
@@ -172,13 +559,127 @@ impl<T> ToCustomTokens for [T]
 where
     T: ToCustomTokens
 {
-    default fn to_custom_tokens(&self, tokens: &mut Tokens) {
-        tokens.append("[");
-        self.iter().for_each(|item| {
-            item.to_custom_tokens(tokens);
-            tokens.append(",");
+    default fn to_custom_tokens_with(&self, ctx: &mut Context) {
+        if ctx.options().typed && self.is_empty() {
+            append_empty_array::<T>(ctx);
+            return;
+        }
+        if self.is_repeat_array() {
+            append_repeat_array(ctx, self);
+            return;
+        }
+        if self.is_compact(ctx.options()) {
+            self.append_compact(ctx, false);
+            return;
+        }
+        ctx.append("[");
+        append_elements(ctx, self, |ctx, index, item| {
+            ctx.tokenize_element(index, item);
+            ctx.append(",");
         });
-        tokens.append("]");
+        ctx.append("]");
+    }
+
+    fn to_type_tokens(ctx: &mut Context) {
+        ctx.append("[");
+        ctx.append_type::<T>();
+        ctx.append("]");
+    }
+
+    default fn impl_name() -> &'static str {
+        "impl for [T]"
+    }
+}
+
+impl ToCustomTokens for [u8] {
+    fn to_custom_tokens_with(&self, ctx: &mut Context) {
+        if ctx.options().typed && self.is_empty() {
+            append_empty_array::<u8>(ctx);
+        } else if self.is_empty() {
+            ctx.append("[]");
+        } else if self.is_repeat_array() {
+            append_repeat_array(ctx, self);
+        } else {
+            ctx.append("(");
+            ctx.append("*");
+            append_byte_string(ctx, self);
+            ctx.append(")");
+        }
+    }
+
+    fn impl_name() -> &'static str {
+        "impl for [u8]"
+    }
+}
+
+fn append_heap_vec<T>(ctx: &mut Context, items: &[T])
+where
+    T: ToCustomTokens
+{
+    ctx.trace_note("heap");
+    ctx.append("{");
+    ctx.append("let");
+    ctx.append("mut");
+    ctx.append("items");
+    ctx.append("=");
+    ctx.append_prelude_path("Vec", "::std::vec::Vec");
+    if ctx.options().typed {
+        ctx.append("::");
+        ctx.append("<");
+        ctx.append_type::<T>();
+        ctx.append(">");
+    }
+    ctx.append("::");
+    ctx.append("with_capacity");
+    ctx.append("(");
+    ctx.append(items.len().to_string());
+    ctx.append(")");
+    ctx.append(";");
+    append_elements(ctx, items, |ctx, index, item| {
+        ctx.append("items");
+        ctx.append(".");
+        ctx.append("push");
+        ctx.append("(");
+        ctx.tokenize_element(index, item);
+        ctx.append(")");
+        ctx.append(";");
+    });
+    ctx.append("items");
+    ctx.append("}");
+}
+
+trait ToVecTokens {
+    fn to_vec_tokens(&self, ctx: &mut Context);
+}
+
+impl<T> ToVecTokens for [T]
+where
+    T: ToCustomTokens
+{
+    default fn to_vec_tokens(&self, ctx: &mut Context) {
+        if T::is_heap_direct() {
+            append_heap_vec(ctx, self);
+        } else if self.is_compact(ctx.options()) {
+            self.append_compact(ctx, true);
+        } else {
+            ctx.append_prelude_path("vec!", "::std::vec!");
+            ctx.tokenize(self);
+        }
+    }
+}
+
+impl ToVecTokens for [u8] {
+    fn to_vec_tokens(&self, ctx: &mut Context) {
+        if self.is_empty() {
+            ctx.append_prelude_path("vec!", "::std::vec!");
+            ctx.tokenize(self);
+        } else {
+            append_byte_string(ctx, self);
+            ctx.append(".");
+            ctx.append("to_vec");
+            ctx.append("(");
+            ctx.append(")");
+        }
     }
 }
 
@@ -186,13 +687,63 @@ impl<'a, T> ToCustomTokens for Cow<'a, T>
 where
     T: ?Sized + ToOwned + ToCustomTokens
 {
-    fn to_custom_tokens(&self, tokens: &mut Tokens) {
-        tokens.append("::std::borrow::Cow");
-        tokens.append("::");
-        tokens.append("from");
-        tokens.append("(");
-        self.as_ref().to_custom_tokens(tokens);
-        tokens.append(")");
+    fn to_custom_tokens_with(&self, ctx: &mut Context) {
+        if ctx.options().constant {
+            ctx.append("::std::borrow::Cow");
+            if ctx.options().typed {
+                ctx.append("::");
+                ctx.append("<");
+                ctx.append_type::<T>();
+                ctx.append(">");
+            }
+            ctx.append("::");
+            ctx.append("Borrowed");
+            ctx.append("(");
+            self.as_ref().to_ref_tokens(ctx);
+            ctx.append(")");
+            return;
+        }
+        if ctx.options().no_implicit_prelude {
+            ctx.append("<");
+            ctx.append("::std::borrow::Cow");
+            ctx.append("<");
+            if ctx.options().typed {
+                ctx.append_type::<T>();
+            } else {
+                ctx.append("_");
+            }
+            ctx.append(">");
+            ctx.append("as");
+            ctx.append("::std::convert::From");
+            ctx.append("<");
+            ctx.append("_");
+            ctx.append(">");
+            ctx.append(">");
+        } else {
+            ctx.append("::std::borrow::Cow");
+            if ctx.options().typed {
+                ctx.append("::");
+                ctx.append("<");
+                ctx.append_type::<T>();
+                ctx.append(">");
+            }
+        }
+        ctx.append("::");
+        ctx.append("from");
+        ctx.append("(");
+        ctx.tokenize(self.as_ref());
+        ctx.append(")");
+    }
+
+    fn to_type_tokens(ctx: &mut Context) {
+        ctx.append("::std::borrow::Cow");
+        ctx.append("<");
+        ctx.append_type::<T>();
+        ctx.append(">");
+    }
+
+    fn impl_name() -> &'static str {
+        "impl for Cow<'a, T>"
     }
 }
 
@@ -200,9 +751,34 @@ impl<T> ToCustomTokens for Box<T>
 where
     T: ?Sized + ToCustomTokens
 {
-    fn to_custom_tokens(&self, tokens: &mut Tokens) {
-        tokens.append("box");
-        (**self).to_custom_tokens(tokens);
+    fn to_custom_tokens_with(&self, ctx: &mut Context) {
+        if ctx.options().constant {
+            ctx.fail_not_const::<Self>();
+        }
+        if ctx.options().target_version().is_some() {
+            ctx.append_prelude_path("Box", "::std::boxed::Box");
+            ctx.append("::");
+            ctx.append("new");
+            ctx.append("(");
+            ctx.tokenize(&**self);
+            ctx.append(")");
+        } else {
+            ctx.append("(");
+            ctx.append("box");
+            ctx.tokenize(&**self);
+            ctx.append(")");
+        }
+    }
+
+    fn to_type_tokens(ctx: &mut Context) {
+        ctx.append_prelude_path("Box", "::std::boxed::Box");
+        ctx.append("<");
+        ctx.append_type::<T>();
+        ctx.append(">");
+    }
+
+    fn impl_name() -> &'static str {
+        "impl for Box<T>"
     }
 }
 
@@ -210,14 +786,28 @@ impl<T> ToCustomTokens for Rc<T>
 where
     T: ?Sized + ToCustomTokens
 {
-    fn to_custom_tokens(&self, tokens: &mut Tokens) {
+    fn to_custom_tokens_with(&self, ctx: &mut Context) {
         assert_eq!(Rc::strong_count(self), 1, "Unsound operation");
-        tokens.append("::std::rc::Rc");
-        tokens.append("::");
-        tokens.append("new");
-        tokens.append("(");
-        self.as_ref().to_custom_tokens(tokens);
-        tokens.append(")");
+        if ctx.options().constant {
+            ctx.fail_not_const::<Self>();
+        }
+        ctx.append("::std::rc::Rc");
+        ctx.append("::");
+        ctx.append("new");
+        ctx.append("(");
+        ctx.tokenize(self.as_ref());
+        ctx.append(")");
+    }
+
+    fn to_type_tokens(ctx: &mut Context) {
+        ctx.append("::std::rc::Rc");
+        ctx.append("<");
+        ctx.append_type::<T>();
+        ctx.append(">");
+    }
+
+    fn impl_name() -> &'static str {
+        "impl for Rc<T>"
     }
 }
 
@@ -225,14 +815,28 @@ impl<T> ToCustomTokens for Arc<T>
 where
     T: ?Sized + ToCustomTokens
 {
-    fn to_custom_tokens(&self, tokens: &mut Tokens) {
+    fn to_custom_tokens_with(&self, ctx: &mut Context) {
         assert_eq!(Arc::strong_count(self), 1, "Unsound operation");
-        tokens.append("::std::sync::Arc");
-        tokens.append("::");
-        tokens.append("new");
-        tokens.append("(");
-        self.as_ref().to_custom_tokens(tokens);
-        tokens.append(")");
+        if ctx.options().constant {
+            ctx.fail_not_const::<Self>();
+        }
+        ctx.append("::std::sync::Arc");
+        ctx.append("::");
+        ctx.append("new");
+        ctx.append("(");
+        ctx.tokenize(self.as_ref());
+        ctx.append(")");
+    }
+
+    fn to_type_tokens(ctx: &mut Context) {
+        ctx.append("::std::sync::Arc");
+        ctx.append("<");
+        ctx.append_type::<T>();
+        ctx.append(">");
+    }
+
+    fn impl_name() -> &'static str {
+        "impl for Arc<T>"
     }
 }
 
@@ -241,19 +845,24 @@ where
     T: Eq + Hash + ToCustomTokens,
     U: ToCustomTokens
 {
-    fn to_custom_tokens(&self, tokens: &mut Tokens) {
-        tokens.append("::std::collections::HashMap");
-        tokens.append("::");
-        tokens.append("from_iter");
-        tokens.append("(");
-        tokens.append("vec!");
-        tokens.append("[");
-        self.iter().for_each(|entry| {
-            entry.to_custom_tokens(tokens);
-            tokens.append(",");
-        });
-        tokens.append("]");
-        tokens.append(")");
+    fn to_custom_tokens_with(&self, ctx: &mut Context) {
+        if ctx.options().constant {
+            ctx.fail_not_const::<Self>();
+        }
+        append_map::<T, U, _>(ctx, "::std::collections::HashMap", self.len(), self.iter());
+    }
+
+    fn to_type_tokens(ctx: &mut Context) {
+        ctx.append("::std::collections::HashMap");
+        ctx.append("<");
+        ctx.append_type::<T>();
+        ctx.append(",");
+        ctx.append_type::<U>();
+        ctx.append(">");
+    }
+
+    fn impl_name() -> &'static str {
+        "impl for HashMap<T, U>"
     }
 }
 
@@ -262,20 +871,109 @@ where
     T: ToCustomTokens,
     U: ToCustomTokens
 {
-    fn to_custom_tokens(&self, tokens: &mut Tokens) {
-        tokens.append("::std::collections::BTreeMap");
-        tokens.append("::");
-        tokens.append("from_iter");
-        tokens.append("(");
-        tokens.append("vec!");
-        tokens.append("[");
-        self.iter().for_each(|entry| {
-            entry.to_custom_tokens(tokens);
-            tokens.append(",");
-        });
-        tokens.append("]");
-        tokens.append(")");
+    fn to_custom_tokens_with(&self, ctx: &mut Context) {
+        if ctx.options().constant {
+            if self.is_empty() && ctx.options().supports(1, 66) {
+                ctx.append("::std::collections::BTreeMap");
+                if ctx.options().typed {
+                    ctx.append("::");
+                    append_map_type_arguments::<T, U>(ctx);
+                }
+                ctx.append("::");
+                ctx.append("new");
+                ctx.append("(");
+                ctx.append(")");
+                return;
+            }
+            ctx.fail_not_const::<Self>();
+        }
+        append_map::<T, U, _>(ctx, "::std::collections::BTreeMap", self.len(), self.iter());
+    }
+
+    fn to_type_tokens(ctx: &mut Context) {
+        ctx.append("::std::collections::BTreeMap");
+        ctx.append("<");
+        ctx.append_type::<T>();
+        ctx.append(",");
+        ctx.append_type::<U>();
+        ctx.append(">");
+    }
+
+    fn impl_name() -> &'static str {
+        "impl for BTreeMap<T, U>"
+    }
+}
+
+fn append_map<'a, T, U, I>(ctx: &mut Context, path: &str, len: usize, entries: I)
+where
+    T: 'a + ToCustomTokens,
+    U: 'a + ToCustomTokens,
+    I: Iterator<Item = (&'a T, &'a U)>
+{
+    let from_array = ctx.options().prefers(1, 56);
+    if from_array && !ctx.options().no_implicit_prelude {
+        ctx.append(path);
+        if ctx.options().typed {
+            ctx.append("::");
+            append_map_type_arguments::<T, U>(ctx);
+        }
+    } else {
+        ctx.append("<");
+        ctx.append(path);
+        append_map_type_arguments::<T, U>(ctx);
+        ctx.append("as");
+        if from_array {
+            ctx.append("::std::convert::From");
+            ctx.append("<");
+            ctx.append("[");
+            ctx.append("_");
+            ctx.append(";");
+            ctx.append(len.to_string());
+            ctx.append("]");
+            ctx.append(">");
+        } else {
+            ctx.append("::std::iter::FromIterator");
+            ctx.append("<");
+            ctx.append("_");
+            ctx.append(">");
+        }
+        ctx.append(">");
+    }
+    ctx.append("::");
+    if from_array {
+        ctx.append("from");
+        ctx.append("(");
+    } else {
+        ctx.append("from_iter");
+        ctx.append("(");
+        ctx.append_prelude_path("vec!", "::std::vec!");
+    }
+    ctx.append("[");
+    let entries: Vec<_> = entries.collect();
+    append_elements(ctx, &entries, |ctx, index, entry| {
+        ctx.tokenize_element(index, entry);
+        ctx.append(",");
+    });
+    ctx.append("]");
+    ctx.append(")");
+}
+
+fn append_map_type_arguments<T, U>(ctx: &mut Context)
+where
+    T: ToCustomTokens,
+    U: ToCustomTokens
+{
+    ctx.append("<");
+    if ctx.options().typed {
+        ctx.append_type::<T>();
+        ctx.append(",");
+        ctx.append_type::<U>();
+    } else {
+        ctx.append("_");
+        ctx.append(",");
+        ctx.append("_");
     }
+    ctx.append(">");
 }
 
 // Third party
@@ -284,12 +982,48 @@ impl<T> ToCustomTokens for OrderedFloat<T>
 where
     T: ?Sized + ToCustomTokens + Float
 {
-    fn to_custom_tokens(&self, tokens: &mut Tokens) {
-        self.as_ref().to_custom_tokens(tokens);
-        tokens.append(".");
-        tokens.append("into");
-        tokens.append("(");
-        tokens.append(")");
+    fn to_custom_tokens_with(&self, ctx: &mut Context) {
+        if ctx.options().typed && !ctx.options().constant {
+            if ctx.options().no_implicit_prelude {
+                ctx.append("<");
+                ctx.append("::ordered_float::OrderedFloat");
+                ctx.append("<");
+                ctx.append_type::<T>();
+                ctx.append(">");
+                ctx.append("as");
+                ctx.append("::std::convert::From");
+                ctx.append("<");
+                ctx.append_type::<T>();
+                ctx.append(">");
+                ctx.append(">");
+            } else {
+                ctx.append("::ordered_float::OrderedFloat");
+                ctx.append("::");
+                ctx.append("<");
+                ctx.append_type::<T>();
+                ctx.append(">");
+            }
+            ctx.append("::");
+            ctx.append("from");
+        } else {
+            // Unlike `.into()`, the constructor names its type, so the value
+            // can be a method receiver.
+            ctx.append("::ordered_float::OrderedFloat");
+        }
+        ctx.append("(");
+        ctx.tokenize(self.as_ref());
+        ctx.append(")");
+    }
+
+    fn to_type_tokens(ctx: &mut Context) {
+        ctx.append("::ordered_float::OrderedFloat");
+        ctx.append("<");
+        ctx.append_type::<T>();
+        ctx.append(">");
+    }
+
+    fn impl_name() -> &'static str {
+        "impl for OrderedFloat<T>"
     }
 }
 
@@ -297,28 +1031,58 @@ impl<T: Array> ToCustomTokens for SmallVec<T>
 where
     T::Item: ToCustomTokens
 {
-    fn to_custom_tokens(&self, tokens: &mut Tokens) {
+    fn to_custom_tokens_with(&self, ctx: &mut Context) {
+        if ctx.options().constant {
+            ctx.fail_not_const::<Self>();
+        }
+        ctx.append("::smallvec::SmallVec");
+        // Unlike `from_buf`, `from_vec` only gets the inline size from the type.
+        let from_buf = !self.is_empty() && self.inline_size() == self.len();
+        if ctx.options().typed || (!self.is_empty() && !from_buf) {
+            ctx.append("::");
+            ctx.append("<");
+            append_small_vec_array_type::<T>(ctx);
+            ctx.append(">");
+        }
         if self.is_empty() {
-            tokens.append("SmallVec");
-            tokens.append("::");
-            tokens.append("new");
-            tokens.append("(");
-            tokens.append(")");
-        } else if self.inline_size() == self.len() {
-            tokens.append("SmallVec");
-            tokens.append("::");
-            tokens.append("from_buf");
-            tokens.append("(");
-            self.as_ref().to_custom_tokens(tokens);
-            tokens.append(")");
+            ctx.append("::");
+            ctx.append("new");
+            ctx.append("(");
+            ctx.append(")");
+        } else if from_buf {
+            ctx.append("::");
+            ctx.append("from_buf");
+            ctx.append("(");
+            ctx.tokenize(self.as_ref());
+            ctx.append(")");
         } else {
-            tokens.append("SmallVec");
-            tokens.append("::");
-            tokens.append("from_vec");
-            tokens.append("(");
-            tokens.append("vec!");
-            self.as_ref().to_custom_tokens(tokens);
-            tokens.append(")");
+            ctx.append("::");
+            ctx.append("from_vec");
+            ctx.append("(");
+            self.as_ref().to_vec_tokens(ctx);
+            ctx.append(")");
         }
     }
+
+    fn to_type_tokens(ctx: &mut Context) {
+        ctx.append("::smallvec::SmallVec");
+        ctx.append("<");
+        append_small_vec_array_type::<T>(ctx);
+        ctx.append(">");
+    }
+
+    fn impl_name() -> &'static str {
+        "impl for SmallVec<T>"
+    }
+}
+
+fn append_small_vec_array_type<T: Array>(ctx: &mut Context)
+where
+    T::Item: ToCustomTokens
+{
+    ctx.append("[");
+    ctx.append_type::<T::Item>();
+    ctx.append(";");
+    ctx.append(T::size().to_string());
+    ctx.append("]");
 }
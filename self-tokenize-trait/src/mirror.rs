@@ -0,0 +1,346 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use num_traits::Float;
+use ordered_float::OrderedFloat;
+use smallvec::{Array, SmallVec};
+
+use bytes::append_byte_string;
+use context::Context;
+
+/// Types with a borrowed, allocation free counterpart that can be initialized
+/// in a `static`. `#[self_tokenize(mirror)]` derives this for `Foo` along with
+/// the `FooStatic` mirror type and a `From<&FooStatic>` conversion back.
+pub trait StaticMirror: Sized {
+    type Static: 'static;
+
+    /// Emits a const expression of type `Self::Static`.
+    fn to_static_tokens(&self, ctx: &mut Context);
+
+    fn from_static(value: &Self::Static) -> Self;
+}
+
+macro_rules! copy_impls {
+    ( $( $ty:ty )+ ) => { $(
+        impl StaticMirror for $ty {
+            type Static = $ty;
+
+            fn to_static_tokens(&self, ctx: &mut Context) {
+                ctx.tokenize(self);
+            }
+
+            fn from_static(value: &$ty) -> Self {
+                *value
+            }
+        }
+    )+ };
+}
+
+macro_rules! tuple_impls {
+    ( $( ( $( $idx:tt: $T:ident ),* ) )+ ) => { $(
+        impl<$( $T ),*> StaticMirror for ($( $T, )*)
+        where
+            $( $T: StaticMirror ),*
+        {
+            type Static = ($( $T::Static, )*);
+
+            fn to_static_tokens(&self, ctx: &mut Context) {
+                ctx.append("(");
+                $(
+                    self.$idx.to_static_tokens(ctx);
+                    ctx.append(",");
+                )*
+                ctx.append(")");
+            }
+
+            fn from_static(_value: &Self::Static) -> Self {
+                ($( $T::from_static(&_value.$idx), )*)
+            }
+        }
+    )+ }
+}
+
+trait ToStaticSliceTokens {
+    fn to_static_slice_tokens(&self, ctx: &mut Context);
+}
+
+impl<T> ToStaticSliceTokens for [T]
+where
+    T: StaticMirror
+{
+    default fn to_static_slice_tokens(&self, ctx: &mut Context) {
+        ctx.append("&");
+        ctx.append("[");
+        self.iter().for_each(|item| {
+            item.to_static_tokens(ctx);
+            ctx.append(",");
+        });
+        ctx.append("]");
+    }
+}
+
+impl ToStaticSliceTokens for [u8] {
+    fn to_static_slice_tokens(&self, ctx: &mut Context) {
+        if self.is_empty() {
+            ctx.append("&");
+            ctx.append("[");
+            ctx.append("]");
+        } else {
+            append_byte_string(ctx, self);
+        }
+    }
+}
+
+/// Emits `&{ value }`. Unlike a bare `&value`, the block lets the value coerce,
+/// e.g. a `&[u8; N]` byte string into the `&[u8]` of `Box<Vec<u8>>`'s mirror.
+fn append_static_ref<T>(ctx: &mut Context, value: &T)
+where
+    T: StaticMirror
+{
+    ctx.append("&");
+    ctx.append("{");
+    value.to_static_tokens(ctx);
+    ctx.append("}");
+}
+
+fn append_static_entries<'a, T, U, I>(ctx: &mut Context, entries: I)
+where
+    T: 'a + StaticMirror,
+    U: 'a + StaticMirror,
+    I: Iterator<Item = (&'a T, &'a U)>
+{
+    ctx.append("&");
+    ctx.append("[");
+    entries.for_each(|(key, value)| {
+        ctx.append("(");
+        key.to_static_tokens(ctx);
+        ctx.append(",");
+        value.to_static_tokens(ctx);
+        ctx.append(")");
+        ctx.append(",");
+    });
+    ctx.append("]");
+}
+
+copy_impls!(
+    () bool char i8 u8 i16 u16 i32 u32 i64 u64 f32 f64 isize usize
+);
+
+tuple_impls! {
+    (0: A)
+    (0: A, 1: B)
+    (0: A, 1: B, 2: C)
+    (0: A, 1: B, 2: C, 3: D)
+    (0: A, 1: B, 2: C, 3: D, 4: E)
+    (0: A, 1: B, 2: C, 3: D, 4: E, 5: F)
+    (0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G)
+    (0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G, 7: H)
+}
+
+impl StaticMirror for &'static str {
+    type Static = &'static str;
+
+    fn to_static_tokens(&self, ctx: &mut Context) {
+        ctx.tokenize(*self);
+    }
+
+    fn from_static(value: &&'static str) -> Self {
+        value
+    }
+}
+
+impl StaticMirror for String {
+    type Static = &'static str;
+
+    fn to_static_tokens(&self, ctx: &mut Context) {
+        ctx.tokenize(self.as_str());
+    }
+
+    fn from_static(value: &&'static str) -> Self {
+        value.to_string()
+    }
+}
+
+impl StaticMirror for Cow<'static, str> {
+    type Static = &'static str;
+
+    fn to_static_tokens(&self, ctx: &mut Context) {
+        ctx.tokenize(self.as_ref());
+    }
+
+    fn from_static(value: &&'static str) -> Self {
+        Cow::Borrowed(*value)
+    }
+}
+
+impl<T> StaticMirror for Option<T>
+where
+    T: StaticMirror
+{
+    type Static = Option<T::Static>;
+
+    fn to_static_tokens(&self, ctx: &mut Context) {
+        match *self {
+            Some(ref value) => {
+                ctx.append_prelude_path("Some", "::std::option::Option::Some");
+                ctx.append("(");
+                value.to_static_tokens(ctx);
+                ctx.append(")");
+            }
+            None => {
+                ctx.append_prelude_path("None", "::std::option::Option::None");
+            }
+        }
+    }
+
+    fn from_static(value: &Option<T::Static>) -> Self {
+        value.as_ref().map(T::from_static)
+    }
+}
+
+impl<T> StaticMirror for Vec<T>
+where
+    T: StaticMirror
+{
+    type Static = &'static [T::Static];
+
+    fn to_static_tokens(&self, ctx: &mut Context) {
+        self[..].to_static_slice_tokens(ctx);
+    }
+
+    fn from_static(value: &&'static [T::Static]) -> Self {
+        value.iter().map(T::from_static).collect()
+    }
+}
+
+impl<T> StaticMirror for Box<T>
+where
+    T: StaticMirror
+{
+    type Static = &'static T::Static;
+
+    fn to_static_tokens(&self, ctx: &mut Context) {
+        append_static_ref(ctx, &**self);
+    }
+
+    fn from_static(value: &&'static T::Static) -> Self {
+        Box::new(T::from_static(*value))
+    }
+}
+
+impl<T> StaticMirror for Rc<T>
+where
+    T: StaticMirror
+{
+    type Static = &'static T::Static;
+
+    fn to_static_tokens(&self, ctx: &mut Context) {
+        append_static_ref(ctx, &**self);
+    }
+
+    fn from_static(value: &&'static T::Static) -> Self {
+        Rc::new(T::from_static(*value))
+    }
+}
+
+impl<T> StaticMirror for Arc<T>
+where
+    T: StaticMirror
+{
+    type Static = &'static T::Static;
+
+    fn to_static_tokens(&self, ctx: &mut Context) {
+        append_static_ref(ctx, &**self);
+    }
+
+    fn from_static(value: &&'static T::Static) -> Self {
+        Arc::new(T::from_static(*value))
+    }
+}
+
+impl<T, U> StaticMirror for HashMap<T, U>
+where
+    T: Eq + Hash + StaticMirror,
+    U: StaticMirror
+{
+    type Static = &'static [(T::Static, U::Static)];
+
+    fn to_static_tokens(&self, ctx: &mut Context) {
+        append_static_entries(ctx, self.iter());
+    }
+
+    fn from_static(value: &&'static [(T::Static, U::Static)]) -> Self {
+        value
+            .iter()
+            .map(|(key, value)| (T::from_static(key), U::from_static(value)))
+            .collect()
+    }
+}
+
+impl<T, U> StaticMirror for BTreeMap<T, U>
+where
+    T: Ord + StaticMirror,
+    U: StaticMirror
+{
+    type Static = &'static [(T::Static, U::Static)];
+
+    fn to_static_tokens(&self, ctx: &mut Context) {
+        append_static_entries(ctx, self.iter());
+    }
+
+    fn from_static(value: &&'static [(T::Static, U::Static)]) -> Self {
+        value
+            .iter()
+            .map(|(key, value)| (T::from_static(key), U::from_static(value)))
+            .collect()
+    }
+}
+
+// Third party
+
+impl<T> StaticMirror for OrderedFloat<T>
+where
+    T: 'static + Float + StaticMirror
+{
+    type Static = OrderedFloat<T>;
+
+    fn to_static_tokens(&self, ctx: &mut Context) {
+        ctx.append("::ordered_float::OrderedFloat");
+        ctx.append("(");
+        self.0.to_static_tokens(ctx);
+        ctx.append(")");
+    }
+
+    fn from_static(value: &OrderedFloat<T>) -> Self {
+        *value
+    }
+}
+
+impl<T: Array> StaticMirror for SmallVec<T>
+where
+    T::Item: StaticMirror
+{
+    type Static = &'static [<T::Item as StaticMirror>::Static];
+
+    fn to_static_tokens(&self, ctx: &mut Context) {
+        self.as_ref().to_static_slice_tokens(ctx);
+    }
+
+    fn from_static(value: &Self::Static) -> Self {
+        value.iter().map(T::Item::from_static).collect()
+    }
+}
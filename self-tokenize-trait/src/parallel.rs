@@ -0,0 +1,82 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use context::Context;
+
+/// Collections shorter than this are not worth splitting across threads.
+#[cfg(feature = "parallel")]
+pub const MIN_PARALLEL_LEN: usize = 1024;
+
+/// Runs `f` for every element of `items`, in order. With `Options::parallel`,
+/// large collections of `Sync` elements are split into chunks tokenized on the
+/// rayon thread pool, and the results are joined back in order. A pool with a
+/// single thread could only add the cost of forking and joining.
+pub fn append_elements<T, F>(ctx: &mut Context, items: &[T], f: F)
+where
+    F: Fn(&mut Context, usize, &T) + Sync
+{
+    #[cfg(feature = "parallel")]
+    {
+        let is_worth_it = items.len() >= MIN_PARALLEL_LEN && rayon::current_num_threads() > 1;
+        if ctx.options().parallel && is_worth_it && items.append_parallel(ctx, &f) {
+            return;
+        }
+    }
+    items.iter().enumerate().for_each(|(index, item)| f(ctx, index, item));
+}
+
+#[cfg(feature = "parallel")]
+trait Parallel<T> {
+    fn append_parallel<F>(&self, ctx: &mut Context, f: &F) -> bool
+    where
+        F: Fn(&mut Context, usize, &T) + Sync;
+}
+
+#[cfg(feature = "parallel")]
+impl<T> Parallel<T> for [T] {
+    default fn append_parallel<F>(&self, _: &mut Context, _: &F) -> bool
+    where
+        F: Fn(&mut Context, usize, &T) + Sync
+    {
+        false
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl<T: Sync> Parallel<T> for [T] {
+    fn append_parallel<F>(&self, ctx: &mut Context, f: &F) -> bool
+    where
+        F: Fn(&mut Context, usize, &T) + Sync
+    {
+        let fork = match ctx.fork() {
+            Some(fork) => fork,
+            None => return false
+        };
+        // A few chunks per thread keeps the pool busy when elements vary in size.
+        let chunk_size = (self.len() / (rayon::current_num_threads() * 4)).max(MIN_PARALLEL_LEN / 4);
+        let joined: Vec<_> = self.par_chunks(chunk_size)
+            .enumerate()
+            .map(|(chunk, items)| {
+                fork.run(|ctx| {
+                    items
+                        .iter()
+                        .enumerate()
+                        .for_each(|(index, item)| f(ctx, chunk * chunk_size + index, item))
+                })
+            })
+            .collect();
+        joined.into_iter().for_each(|joined| ctx.join(joined));
+        true
+    }
+}
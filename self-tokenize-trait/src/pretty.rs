@@ -0,0 +1,722 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::mem;
+use std::ops::Range;
+
+/// Array elements up to this long are packed several to a line, as rustfmt
+/// does for numeric arrays.
+const MAX_PACKED_ITEM_LEN: usize = 10;
+
+/// Punctuation of more than one character, longest first. `<<` and `>>` are
+/// missing on purpose: they are far more likely to close nested generics.
+const PUNCTS: &[&str] = &[
+    "...", "..=", "::", "->", "=>", "==", "!=", "<=", ">=", "&&", "||", "+=", "-=", "*=", "/=", "%=", "^=", "&=", "|=", ".."
+];
+
+/// Keywords that are separated from a following `(` or `[`.
+const KEYWORDS: &[&str] = &[
+    "as", "box", "break", "const", "else", "for", "if", "in", "let", "loop", "match", "move", "mut", "ref", "return", "static", "where",
+    "while"
+];
+
+/// Statements starting with these end with their block instead of a `;`.
+const BLOCK_KEYWORDS: &[&str] = &["else", "fn", "for", "if", "impl", "loop", "mod", "trait", "unsafe", "while"];
+
+/// Layout of `pretty_print`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrettyOptions {
+    /// Lines are broken to stay within this many characters where possible.
+    pub max_width: usize,
+    /// Spaces per level of indentation.
+    pub indent: usize,
+    /// Put a comma after the last item of lists broken over several lines.
+    pub trailing_commas: bool
+}
+
+impl Default for PrettyOptions {
+    /// The defaults of rustfmt.
+    fn default() -> Self {
+        PrettyOptions {
+            max_width: 100,
+            indent: 4,
+            trailing_commas: true
+        }
+    }
+}
+
+/// Lays out the output of a `Context`, e.g. `Tokens::to_string()`, the way
+/// rustfmt would: struct literals, arrays, calls and blocks that do not fit on
+/// a line get one item per line, and short array elements are packed.
+pub fn pretty_print(code: &str, options: PrettyOptions) -> String {
+    layout(code, options).code
+}
+
+/// Pretty-printed code, and where each token of the original code ended up.
+pub struct Layout {
+    pub code: String,
+    // (original start, new start, length) of each token, in order.
+    moves: Vec<(usize, usize, usize)>
+}
+
+pub fn layout(code: &str, options: PrettyOptions) -> Layout {
+    let tokens = lex(code);
+    let trees = parse(&tokens);
+    let mut printer = Printer {
+        tokens: &tokens,
+        options,
+        out: String::with_capacity(code.len() + code.len() / 4),
+        line_start: 0,
+        level: 0,
+        at_line_start: true,
+        spacing: Spacing::default(),
+        moves: Vec::with_capacity(tokens.len())
+    };
+    printer.print_statements(&trees);
+    Layout {
+        code: printer.out,
+        moves: printer.moves
+    }
+}
+
+impl Layout {
+    /// Where a byte range of the original code is in the pretty-printed code.
+    pub fn map_span(&self, span: Range<usize>) -> Range<usize> {
+        let start = self.map_offset(span.start);
+        if span.end > span.start {
+            start..self.map_offset(span.end - 1) + 1
+        } else {
+            start..start
+        }
+    }
+
+    // Offsets between tokens move to the start of the next token.
+    fn map_offset(&self, offset: usize) -> usize {
+        let index = self.moves.partition_point(|&(start, _, len)| start + len <= offset);
+        match self.moves.get(index) {
+            Some(&(start, new_start, _)) if start <= offset => new_start + offset - start,
+            Some(&(_, new_start, _)) => new_start,
+            None => self.code.len()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Ident,
+    Literal,
+    Lifetime,
+    Punct,
+    Open,
+    Close,
+    // The `<` and `>` around generic arguments, or a qualified path such as
+    // `<T as Trait>`, as opposed to comparisons.
+    OpenAngle,
+    CloseAngle
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    start: usize,
+    text: &'a str,
+    kind: Kind,
+    // Number of angle brackets the token is inside of, not counting its own.
+    generics: usize
+}
+
+fn lex(code: &str) -> Vec<Token<'_>> {
+    let mut tokens: Vec<Token> = vec![];
+    let mut start = 0;
+    let mut generics = 0;
+    while let Some(c) = code[start..].chars().next() {
+        if c.is_whitespace() {
+            start += c.len_utf8();
+            continue;
+        }
+        let rest = &code[start..];
+        let (len, kind) = if let Some(len) = literal_len(rest) {
+            (len, Kind::Literal)
+        } else if c == '\'' {
+            (1 + ident_len(&rest[1..]), Kind::Lifetime)
+        } else if let Some(raw) = rest.strip_prefix("r#") {
+            (2 + ident_len(raw), Kind::Ident)
+        } else if c == '_' || c.is_alphabetic() {
+            (ident_len(rest), Kind::Ident)
+        } else if c.is_ascii_digit() {
+            (number_len(rest), Kind::Literal)
+        } else if "([{".contains(c) {
+            (1, Kind::Open)
+        } else if ")]}".contains(c) {
+            (1, Kind::Close)
+        } else {
+            let len = PUNCTS
+                .iter()
+                .find(|punct| rest.starts_with(*punct))
+                .map_or(c.len_utf8(), |punct| punct.len());
+            match &rest[..len] {
+                "<" if opens_generics(tokens.last()) => (len, Kind::OpenAngle),
+                ">" if generics > 0 => (len, Kind::CloseAngle),
+                _ => (len, Kind::Punct)
+            }
+        };
+        if kind == Kind::CloseAngle {
+            generics -= 1;
+        }
+        tokens.push(Token {
+            start,
+            text: &rest[..len],
+            kind,
+            generics
+        });
+        match &rest[..len] {
+            _ if kind == Kind::OpenAngle => generics += 1,
+            "{" | "}" | ";" => generics = 0,
+            _ => {}
+        }
+        start += len;
+    }
+    tokens
+}
+
+/// Whether a `<` following `prev` opens generic arguments, as in `Vec<u8>`,
+/// `::<u8>` or `(<T as Trait>::f)`, rather than comparing `prev` to something.
+fn opens_generics(prev: Option<&Token>) -> bool {
+    let prev = match prev {
+        Some(prev) => prev,
+        None => return true
+    };
+    match prev.kind {
+        Kind::Ident if KEYWORDS.contains(&prev.text) => true,
+        Kind::Ident => prev.text.starts_with(char::is_uppercase) || is_primitive(prev.text) || prev.text == "impl",
+        Kind::Literal | Kind::Lifetime | Kind::Close | Kind::CloseAngle => false,
+        Kind::Punct | Kind::Open | Kind::OpenAngle => true
+    }
+}
+
+fn ident_len(code: &str) -> usize {
+    code.find(|c: char| c != '_' && !c.is_alphanumeric()).unwrap_or(code.len())
+}
+
+fn number_len(code: &str) -> usize {
+    let hex = code.starts_with("0x");
+    let mut seen_dot = false;
+    let mut prev = '0';
+    for (offset, c) in code.char_indices() {
+        let next_is_digit = code[offset + c.len_utf8()..].starts_with(|c: char| c.is_ascii_digit());
+        let continues = match c {
+            '_' => true,
+            c if c.is_alphanumeric() => true,
+            '.' if !seen_dot && !hex && next_is_digit => {
+                seen_dot = true;
+                true
+            }
+            '+' | '-' => !hex && (prev == 'e' || prev == 'E'),
+            _ => false
+        };
+        if !continues {
+            return offset;
+        }
+        prev = c;
+    }
+    code.len()
+}
+
+/// Length of the string, byte string, raw string or character literal at the
+/// start of `code`. Unterminated literals run to the end.
+fn literal_len(code: &str) -> Option<usize> {
+    let prefix = ["br", "cr", "b", "c", "r", ""]
+        .iter()
+        .find(|prefix| code.starts_with(*prefix) && code[prefix.len()..].starts_with(['"', '\'', '#']))?
+        .len();
+    let raw = code[..prefix].ends_with('r');
+    let rest = &code[prefix..];
+    if raw {
+        let hashes = rest.len() - rest.trim_start_matches('#').len();
+        if !rest[hashes..].starts_with('"') {
+            return None;
+        }
+        let terminator = format!("\"{}", &rest[..hashes]);
+        return Some(rest[hashes + 1..].find(&terminator).map_or(code.len(), |end| prefix + hashes + 1 + end + terminator.len()));
+    }
+    let quote = rest.chars().next()?;
+    if quote == '#' {
+        return None;
+    }
+    if quote == '\'' {
+        let mut chars = rest[1..].chars();
+        match (chars.next(), chars.next()) {
+            (Some('\\'), _) => {}
+            (Some(_), Some('\'')) => {}
+            _ => return None
+        }
+    }
+    let mut escaped = false;
+    for (offset, c) in rest.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == quote => return Some(prefix + offset + 1),
+            _ => {}
+        }
+    }
+    Some(code.len())
+}
+
+enum Tree {
+    Token(usize),
+    Group(Group)
+}
+
+struct Group {
+    open: usize,
+    close: Option<usize>,
+    children: Vec<Tree>,
+    // A block of statements or items rather than a list of expressions, e.g.
+    // a function body. Blocks are never printed on a single line.
+    block: bool,
+    // Part of a type or a visibility, which are kept on one line.
+    in_type: bool,
+    // A list that stays valid with a comma after its last item.
+    takes_trailing_comma: bool
+}
+
+fn parse(tokens: &[Token]) -> Vec<Tree> {
+    let mut open_groups: Vec<(usize, Vec<Tree>)> = vec![];
+    let mut trees = vec![];
+    for (index, token) in tokens.iter().enumerate() {
+        let closes_group = token.kind == Kind::Close &&
+            open_groups
+                .last()
+                .map_or(false, |&(open, _)| closing_delimiter(tokens[open].text) == token.text);
+        if token.kind == Kind::Open {
+            open_groups.push((index, mem::take(&mut trees)));
+        } else if closes_group {
+            let (open, parent) = open_groups.pop().unwrap();
+            let children = mem::replace(&mut trees, parent);
+            let group = new_group(tokens, &trees, open, Some(index), children);
+            trees.push(Tree::Group(group));
+        } else {
+            trees.push(Tree::Token(index));
+        }
+    }
+    while let Some((open, parent)) = open_groups.pop() {
+        let children = mem::replace(&mut trees, parent);
+        let group = new_group(tokens, &trees, open, None, children);
+        trees.push(Tree::Group(group));
+    }
+    trees
+}
+
+fn closing_delimiter(open: &str) -> &'static str {
+    match open {
+        "(" => ")",
+        "[" => "]",
+        _ => "}"
+    }
+}
+
+fn new_group(tokens: &[Token], preceding: &[Tree], open: usize, close: Option<usize>, children: Vec<Tree>) -> Group {
+    let block = tokens[open].text == "{" &&
+        (children.iter().any(|tree| is_token(tokens, tree, ";")) ||
+            preceding.last().map_or(false, |tree| is_token(tokens, tree, "!")) ||
+            BLOCK_KEYWORDS.contains(&statement_head(tokens, preceding, open)));
+    let in_type = !block && in_type(tokens, preceding, open);
+    let takes_trailing_comma = !block && !in_type && takes_trailing_comma(tokens, preceding, open, &children);
+    Group {
+        open,
+        close,
+        children,
+        block,
+        in_type,
+        takes_trailing_comma
+    }
+}
+
+/// Whether the group is part of a type or a visibility, as in `Box<[u8]>`,
+/// `-> (i64, i64)`, `static X: &[u8]` or `pub(crate)`.
+fn in_type(tokens: &[Token], preceding: &[Tree], open: usize) -> bool {
+    let depth = tokens[open].generics;
+    if depth > 0 {
+        return true;
+    }
+    let statement = &preceding[statement_start(tokens, preceding, open)..];
+    for tree in statement.iter().rev() {
+        let token = match *tree {
+            Tree::Token(index) if tokens[index].generics == depth => tokens[index],
+            _ => continue
+        };
+        match token.text {
+            "->" | "as" | "pub" => return true,
+            ":" => return statement.iter().any(|tree| ["let", "static", "const"].iter().any(|keyword| is_token(tokens, tree, keyword))),
+            "=" | "=>" => return false,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Whether the group is a list: the arguments of a call, a tuple, the
+/// elements of an array or `vec![..]`, or the fields of a struct literal.
+/// Parentheses around a single expression, e.g. `(-5i64)` or `box (*b"..")`,
+/// turn into a tuple with a trailing comma, and a comma in an index, a slice
+/// type or a block does not parse at all.
+fn takes_trailing_comma(tokens: &[Token], preceding: &[Tree], open: usize, children: &[Tree]) -> bool {
+    if children.iter().any(|tree| is_token(tokens, tree, ";")) {
+        return false;
+    }
+    if children.iter().any(|tree| is_separator(tokens, tree, open)) {
+        return true;
+    }
+    match (tokens[open].text, preceding.last()) {
+        ("(", Some(&Tree::Token(index))) => {
+            let prev = tokens[index];
+            prev.kind == Kind::Ident && !KEYWORDS.contains(&prev.text) || prev.text == ">" || prev.text == "!"
+        }
+        ("(", Some(&Tree::Group(_))) => true,
+        ("(", None) => false,
+        ("[", Some(&Tree::Token(index))) => tokens[index].text == "!",
+        ("[", _) => false,
+        _ => match (children.first(), children.get(1)) {
+            (Some(&Tree::Token(name)), Some(colon)) => tokens[name].kind == Kind::Ident && is_token(tokens, colon, ":"),
+            _ => false
+        }
+    }
+}
+
+fn is_token(tokens: &[Token], tree: &Tree, text: &str) -> bool {
+    match *tree {
+        Tree::Token(index) => tokens[index].text == text,
+        Tree::Group(_) => false
+    }
+}
+
+/// Whether the tree is a comma between the items of the group opened by
+/// `open`, rather than one between generic arguments, as in `HashMap<K, V>`.
+fn is_separator(tokens: &[Token], tree: &Tree, open: usize) -> bool {
+    match *tree {
+        Tree::Token(index) => tokens[index].text == "," && tokens[index].generics == tokens[open].generics,
+        Tree::Group(_) => false
+    }
+}
+
+/// Where the statement or list item that `preceding` ends with starts, in the
+/// group opened by `open`.
+fn statement_start(tokens: &[Token], preceding: &[Tree], open: usize) -> usize {
+    preceding
+        .iter()
+        .rposition(|tree| match *tree {
+            Tree::Token(index) => tokens[index].text == ";" || is_separator(tokens, tree, open),
+            Tree::Group(ref group) => group.block
+        })
+        .map_or(0, |end| end + 1)
+}
+
+/// The first keyword of the statement `preceding` ends with, skipping
+/// attributes, visibility and qualifiers such as `const` in `const fn`.
+fn statement_head<'a>(tokens: &[Token<'a>], preceding: &[Tree], open: usize) -> &'a str {
+    let start = statement_start(tokens, preceding, open);
+    for tree in &preceding[start..] {
+        match *tree {
+            Tree::Token(index) => match tokens[index].text {
+                "#" | "pub" | "!" => {}
+                "const" | "unsafe" | "async" | "extern" | "default" => {}
+                text => return text
+            },
+            Tree::Group(_) => {}
+        }
+    }
+    ""
+}
+
+/// Decides whether tokens printed on the same line are separated by a space.
+#[derive(Debug, Clone, Copy, Default)]
+struct Spacing<'a> {
+    prev: Option<Token<'a>>,
+    // The previous token is a unary operator, the `!` of a macro call or an
+    // opening `<` of generic arguments.
+    prev_binds: bool
+}
+
+impl<'a> Spacing<'a> {
+    fn space_before(&self, token: &Token) -> bool {
+        let prev = match self.prev {
+            Some(prev) => prev,
+            None => return false
+        };
+        let prev_keyword = prev.kind == Kind::Ident && KEYWORDS.contains(&prev.text);
+        // An identifier, or a path or type ending in one.
+        let prev_name = prev.kind == Kind::Ident && !prev_keyword || prev.kind == Kind::CloseAngle;
+        if self.prev_binds {
+            return prev.text == "!" && token.text == "{";
+        }
+        match prev.text {
+            "::" | "." | "#" | ".." | "..=" => return false,
+            "(" | "[" => return false,
+            "{" => return token.text != "}",
+            _ => {}
+        }
+        match token.text {
+            "," | ";" | "." | "?" | ":" | ")" | "]" => false,
+            "}" => true,
+            "::" | "(" | "[" => !prev_name && prev.text != ")" && prev.text != "]",
+            "!" => prev.kind != Kind::Ident || prev_keyword,
+            "<" if token.kind == Kind::OpenAngle => !prev_name || prev.kind == Kind::CloseAngle,
+            ">" => token.kind != Kind::CloseAngle,
+            _ => true
+        }
+    }
+
+    fn is_unary(&self, token: &Token) -> bool {
+        let operand_before = match self.prev {
+            Some(prev) => match prev.kind {
+                Kind::Ident => !KEYWORDS.contains(&prev.text),
+                Kind::Literal | Kind::Lifetime | Kind::Close | Kind::CloseAngle => true,
+                Kind::Open | Kind::OpenAngle | Kind::Punct => false
+            },
+            None => false
+        };
+        match token.text {
+            "&" | "&&" | "*" | "-" => !operand_before,
+            "!" => true,
+            _ => false
+        }
+    }
+
+    fn advance(&mut self, token: Token<'a>) {
+        self.prev_binds = token.kind == Kind::OpenAngle || self.is_unary(&token);
+        self.prev = Some(token);
+    }
+}
+
+fn is_primitive(ident: &str) -> bool {
+    match ident {
+        "bool" | "char" | "str" | "f32" | "f64" => true,
+        _ => {
+            (ident.starts_with('u') || ident.starts_with('i')) &&
+                ["8", "16", "32", "64", "128", "size"].contains(&&ident[1..])
+        }
+    }
+}
+
+struct Printer<'a, 'b> {
+    tokens: &'b [Token<'a>],
+    options: PrettyOptions,
+    out: String,
+    line_start: usize,
+    level: usize,
+    at_line_start: bool,
+    spacing: Spacing<'a>,
+    moves: Vec<(usize, usize, usize)>
+}
+
+impl<'a, 'b> Printer<'a, 'b> {
+    fn print_statements(&mut self, trees: &[Tree]) {
+        let mut start = 0;
+        for end in 0..trees.len() {
+            let ends_statement = match trees[end] {
+                Tree::Token(index) => self.tokens[index].text == ";",
+                Tree::Group(ref group) => group.block || self.is_attribute(&trees[start..end])
+            };
+            if ends_statement || end + 1 == trees.len() {
+                if start > 0 {
+                    self.newline();
+                }
+                self.print_sequence(&trees[start..end + 1], 0);
+                start = end + 1;
+            }
+        }
+    }
+
+    fn is_attribute(&self, trees: &[Tree]) -> bool {
+        match trees.first() {
+            Some(tree) => is_token(self.tokens, tree, "#") && trees[1..].iter().all(|tree| is_token(self.tokens, tree, "!")),
+            None => false
+        }
+    }
+
+    // `tail` is the width of what follows on the same line, e.g. a comma.
+    fn print_sequence(&mut self, trees: &[Tree], tail: usize) {
+        for (position, tree) in trees.iter().enumerate() {
+            match *tree {
+                Tree::Token(index) => self.emit(index),
+                Tree::Group(ref group) => {
+                    let following = &trees[position + 1..];
+                    let tokens = following.iter().take_while(|tree| matches!(**tree, Tree::Token(_))).count();
+                    let tail = following[..tokens]
+                        .iter()
+                        .map(|tree| match *tree {
+                            Tree::Token(index) => self.tokens[index].text.len(),
+                            Tree::Group(_) => 0
+                        })
+                        .sum::<usize>() + if tokens == following.len() { tail } else { 0 };
+                    self.print_group(group, tail);
+                }
+            }
+        }
+    }
+
+    fn print_group(&mut self, group: &Group, tail: usize) {
+        self.emit(group.open);
+        if !group.children.is_empty() {
+            if !group.block && (group.in_type || self.fits(group, tail)) {
+                let mut indices = vec![];
+                self.walk_flat(group, &mut |index| {
+                    indices.push(index);
+                    true
+                });
+                indices.into_iter().for_each(|index| self.emit(index));
+            } else {
+                self.level += 1;
+                self.newline();
+                if group.block {
+                    self.print_statements(&group.children);
+                } else {
+                    self.print_items(group);
+                }
+                self.level -= 1;
+                self.newline();
+            }
+        }
+        if let Some(close) = group.close {
+            self.emit(close);
+        }
+    }
+
+    fn print_items(&mut self, group: &Group) {
+        let mut items = vec![];
+        let mut start = 0;
+        for (position, tree) in group.children.iter().enumerate() {
+            match *tree {
+                Tree::Token(index) if is_separator(self.tokens, tree, group.open) => {
+                    items.push((&group.children[start..position], Some(index)));
+                    start = position + 1;
+                }
+                _ => {}
+            }
+        }
+        if start < group.children.len() {
+            items.push((&group.children[start..], None));
+        }
+        let packed = self.tokens[group.open].text == "[" && items.len() > 1 && items.iter().all(|&(item, _)| self.is_short(item));
+        let keep_comma = self.keeps_last_comma(group);
+        for (position, &(item, comma)) in items.iter().enumerate() {
+            let last = position + 1 == items.len();
+            if position > 0 && (!packed || self.column() + 1 + self.flat_len(item) + 1 > self.options.max_width) {
+                self.newline();
+            }
+            self.print_sequence(item, 1);
+            match comma {
+                Some(comma) if !last || keep_comma || self.options.trailing_commas => self.emit(comma),
+                None if last && self.options.trailing_commas && group.takes_trailing_comma => self.emit_text(","),
+                _ => {}
+            }
+        }
+    }
+
+    fn is_short(&self, item: &[Tree]) -> bool {
+        item.iter().all(|tree| matches!(*tree, Tree::Token(_))) && self.flat_len(item) <= MAX_PACKED_ITEM_LEN
+    }
+
+    fn flat_len(&self, item: &[Tree]) -> usize {
+        item.iter()
+            .map(|tree| match *tree {
+                Tree::Token(index) => self.tokens[index].text.chars().count(),
+                Tree::Group(_) => self.options.max_width
+            })
+            .sum()
+    }
+
+    /// `(x,)` is a tuple, unlike `(x)`.
+    fn keeps_last_comma(&self, group: &Group) -> bool {
+        self.tokens[group.open].text == "(" &&
+            group.children.iter().filter(|tree| is_separator(self.tokens, tree, group.open)).count() == 1 &&
+            group.children.last().map_or(false, |tree| is_separator(self.tokens, tree, group.open))
+    }
+
+    fn fits(&self, group: &Group, tail: usize) -> bool {
+        let available = self.options.max_width.saturating_sub(self.column() + tail);
+        let mut spacing = self.spacing;
+        let mut width = 0;
+        let tokens = self.tokens;
+        let flat = self.walk_flat(group, &mut |index| {
+            let token = tokens[index];
+            if spacing.space_before(&token) {
+                width += 1;
+            }
+            width += token.text.chars().count();
+            spacing.advance(token);
+            width <= available
+        });
+        let close = if self.tokens[group.open].text == "{" { 2 } else { 1 };
+        flat && width + close <= available
+    }
+
+    /// Visits the tokens of `group` on a single line, which drops a trailing
+    /// comma. Stops early if `f` returns false or a block is found.
+    fn walk_flat<F>(&self, group: &Group, f: &mut F) -> bool
+    where
+        F: FnMut(usize) -> bool
+    {
+        let keep_comma = self.keeps_last_comma(group);
+        for (position, tree) in group.children.iter().enumerate() {
+            let complete = match *tree {
+                Tree::Token(index) => {
+                    let trailing_comma = position + 1 == group.children.len() && is_separator(self.tokens, tree, group.open);
+                    (trailing_comma && !keep_comma) || f(index)
+                }
+                Tree::Group(ref inner) => {
+                    !inner.block && f(inner.open) && self.walk_flat(inner, f) && inner.close.map_or(true, &mut *f)
+                }
+            };
+            if !complete {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn column(&self) -> usize {
+        self.out[self.line_start..].chars().count()
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.line_start = self.out.len();
+        let indent = self.level * self.options.indent;
+        self.out.extend((0..indent).map(|_| ' '));
+        self.at_line_start = true;
+    }
+
+    fn emit(&mut self, index: usize) {
+        let token = self.tokens[index];
+        self.emit_token(token);
+        self.moves.push((token.start, self.out.len() - token.text.len(), token.text.len()));
+    }
+
+    // For tokens that are not in the original code.
+    fn emit_text(&mut self, text: &'static str) {
+        self.emit_token(Token {
+            start: 0,
+            text,
+            kind: Kind::Punct,
+            generics: 0
+        });
+    }
+
+    fn emit_token(&mut self, token: Token<'a>) {
+        if !self.at_line_start && self.spacing.space_before(&token) {
+            self.out.push(' ');
+        }
+        self.out.push_str(token.text);
+        self.spacing.advance(token);
+        self.at_line_start = false;
+    }
+}
@@ -0,0 +1,264 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::any::type_name;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use budget::OutputSize;
+use context::{Context, Options};
+use sink::Discard;
+use ToCustomTokens;
+
+/// Where the output of a value comes from, by field path and by type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeReport {
+    /// The whole value. Elements of collections are merged under `[*]`.
+    pub root: SizeNode,
+    /// Output emitted by each type itself, not counting its fields and
+    /// elements, largest first. These add up to the whole value.
+    pub types: Vec<TypeSize>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeNode {
+    /// E.g. `.baz[*].unit`, empty for the value itself.
+    pub path: String,
+    /// Output of every occurrence of this path, including the paths below it.
+    pub bytes: usize,
+    pub tokens: usize,
+    /// Number of values found at this path.
+    pub count: usize,
+    pub children: Vec<SizeNode>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeSize {
+    pub type_name: &'static str,
+    pub bytes: usize,
+    pub tokens: usize,
+    pub count: usize
+}
+
+/// Tokenizes `value` without keeping the output, and reports which fields and
+/// types the output comes from.
+pub fn size_report<T>(value: &T, options: Options) -> SizeReport
+where
+    T: ?Sized + ToCustomTokens
+{
+    let mut discard = Discard;
+    let mut ctx = Context::with_options(&mut discard, options);
+    ctx.profile(|ctx| ctx.tokenize(value))
+}
+
+impl SizeReport {
+    /// One `kind,name,bytes,tokens,count` row per path and per type, where
+    /// `kind` is `path` or `type`.
+    pub fn to_csv(&self) -> String {
+        let mut csv = "kind,name,bytes,tokens,count\n".to_string();
+        self.root.walk(&mut |node, _| {
+            writeln!(csv, "path,{},{},{},{}", quote_csv(&node.path), node.bytes, node.tokens, node.count).unwrap();
+        });
+        self.types.iter().for_each(|size| {
+            writeln!(csv, "type,{},{},{},{}", quote_csv(size.type_name), size.bytes, size.tokens, size.count).unwrap();
+        });
+        csv
+    }
+}
+
+impl SizeNode {
+    fn walk<F>(&self, f: &mut F)
+    where
+        F: FnMut(&SizeNode, usize)
+    {
+        self.walk_at(0, f);
+    }
+
+    fn walk_at<F>(&self, depth: usize, f: &mut F)
+    where
+        F: FnMut(&SizeNode, usize)
+    {
+        f(self, depth);
+        self.children.iter().for_each(|child| child.walk_at(depth + 1, f));
+    }
+}
+
+fn quote_csv(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+fn percent(bytes: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        bytes as f64 * 100.0 / total as f64
+    }
+}
+
+fn times(count: usize) -> String {
+    if count > 1 {
+        format!(" (x{})", count)
+    } else {
+        String::new()
+    }
+}
+
+impl fmt::Display for SizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total = self.root.bytes;
+        writeln!(f, "{:>10} {:>10} {:>7}  path", "bytes", "tokens", "%")?;
+        let mut result = Ok(());
+        self.root.walk(&mut |node, depth| {
+            let name = match (depth, node.path.rfind(['.', '['])) {
+                (0, _) => "value",
+                (_, Some(start)) => &node.path[start..],
+                (_, None) => &node.path[..]
+            };
+            result = result.and_then(|_| {
+                writeln!(
+                    f,
+                    "{:>10} {:>10} {:>6.1}%  {:indent$}{}{}",
+                    node.bytes,
+                    node.tokens,
+                    percent(node.bytes, total),
+                    "",
+                    name,
+                    times(node.count),
+                    indent = depth * 2
+                )
+            });
+        });
+        result?;
+        writeln!(f)?;
+        writeln!(f, "{:>10} {:>10} {:>7}  type", "bytes", "tokens", "%")?;
+        for size in &self.types {
+            writeln!(
+                f,
+                "{:>10} {:>10} {:>6.1}%  {}{}",
+                size.bytes,
+                size.tokens,
+                percent(size.bytes, total),
+                size.type_name,
+                times(size.count)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Accumulates a `SizeReport` while a `Context` tokenizes.
+#[derive(Debug, Default)]
+pub struct Profile {
+    nodes: Vec<Node>,
+    // Nodes of the fields and elements being tokenized, outermost first.
+    open_nodes: Vec<usize>,
+    types: HashMap<&'static str, TypeSize>,
+    // Output of the values nested in each value being tokenized.
+    nested: Vec<OutputSize>
+}
+
+#[derive(Debug)]
+struct Node {
+    segment: String,
+    size: OutputSize,
+    count: usize,
+    children: Vec<usize>
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Profile {
+            nodes: vec![Node {
+                segment: String::new(),
+                size: OutputSize::default(),
+                count: 1,
+                children: vec![]
+            }],
+            open_nodes: vec![0],
+            ..Profile::default()
+        }
+    }
+
+    pub fn enter(&mut self, segment: String) {
+        let parent = *self.open_nodes.last().unwrap();
+        let existing = self.nodes[parent]
+            .children
+            .iter()
+            .cloned()
+            .find(|&child| self.nodes[child].segment == segment);
+        let node = existing.unwrap_or_else(|| {
+            self.nodes.push(Node {
+                segment,
+                size: OutputSize::default(),
+                count: 0,
+                children: vec![]
+            });
+            let node = self.nodes.len() - 1;
+            self.nodes[parent].children.push(node);
+            node
+        });
+        self.open_nodes.push(node);
+    }
+
+    pub fn exit(&mut self, size: OutputSize) {
+        let node = &mut self.nodes[self.open_nodes.pop().unwrap()];
+        node.size.bytes += size.bytes;
+        node.size.tokens += size.tokens;
+        node.count += 1;
+    }
+
+    pub fn begin_value(&mut self) {
+        self.nested.push(OutputSize::default());
+    }
+
+    pub fn end_value<T: ?Sized>(&mut self, size: OutputSize) {
+        let nested = self.nested.pop().unwrap();
+        let name = type_name::<T>();
+        let entry = self.types.entry(name).or_insert(TypeSize {
+            type_name: name,
+            bytes: 0,
+            tokens: 0,
+            count: 0
+        });
+        entry.bytes += size.bytes - nested.bytes;
+        entry.tokens += size.tokens - nested.tokens;
+        entry.count += 1;
+        if let Some(parent) = self.nested.last_mut() {
+            parent.bytes += size.bytes;
+            parent.tokens += size.tokens;
+        }
+    }
+
+    pub fn into_report(mut self, total: OutputSize) -> SizeReport {
+        self.nodes[0].size = total;
+        let mut types: Vec<TypeSize> = self.types.into_values().collect();
+        types.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.type_name.cmp(b.type_name)));
+        SizeReport {
+            root: build_node(&self.nodes, 0, ""),
+            types
+        }
+    }
+}
+
+fn build_node(nodes: &[Node], index: usize, parent_path: &str) -> SizeNode {
+    let node = &nodes[index];
+    let path = format!("{}{}", parent_path, node.segment);
+    SizeNode {
+        children: node.children
+            .iter()
+            .map(|&child| build_node(nodes, child, &path))
+            .collect(),
+        bytes: node.size.bytes,
+        tokens: node.size.tokens,
+        count: node.count,
+        path
+    }
+}
@@ -0,0 +1,201 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use context::Context;
+use {ToCustomTokens, ToVecTokens};
+
+/// Runs of identical elements at least this long are emitted as `[x; n]`,
+/// `vec![x; n]` or `repeat(x).take(n)` instead of element by element.
+pub const MIN_RUN_LEN: usize = 8;
+
+/// Byte strings already cost a single token, so splitting one into a chain only
+/// pays off for much longer runs.
+pub const MIN_BYTE_RUN_LEN: usize = 256;
+
+/// A stretch of a slice that is either a run of a single repeated element, or
+/// a sequence of elements to be emitted one by one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    pub start: usize,
+    pub len: usize,
+    pub repeated: bool
+}
+
+/// Types whose `==` only holds between values that emit the same tokens and
+/// are interchangeable, so that equal elements can be merged into a run. Floats
+/// are left out because `0.0 == -0.0`, `Rc` and `Arc` because the copies of a
+/// run would share one allocation, and other types because their `PartialEq`
+/// may ignore some of what they emit.
+pub trait ExactEq: PartialEq {}
+
+macro_rules! exact_eq_impls {
+    ( $( $ty:ty )+ ) => { $(
+        impl ExactEq for $ty {}
+    )+ }
+}
+
+exact_eq_impls!(
+    bool char i8 u8 i16 u16 i32 u32 i64 u64 isize usize String
+);
+
+impl ExactEq for &str {}
+
+impl<T: ExactEq> ExactEq for Option<T> {}
+
+fn is_uniform<T: ExactEq>(items: &[T]) -> bool {
+    items.len() >= MIN_RUN_LEN && items.iter().all(|item| *item == items[0])
+}
+
+fn find_segments<T: ExactEq>(items: &[T], min_run_len: usize) -> Option<Vec<Segment>> {
+    let mut segments: Vec<Segment> = vec![];
+    let mut start = 0;
+    while start < items.len() {
+        let len = items[start..]
+            .iter()
+            .take_while(|item| **item == items[start])
+            .count();
+        let repeated = len >= min_run_len;
+        let merge = segments
+            .last()
+            .map_or(false, |segment| !segment.repeated && !repeated);
+        if merge {
+            segments.last_mut().unwrap().len += len;
+        } else {
+            segments.push(Segment { start, len, repeated });
+        }
+        start += len;
+    }
+    if segments.iter().any(|segment| segment.repeated) {
+        Some(segments)
+    } else {
+        None
+    }
+}
+
+pub trait RepeatArray {
+    /// Whether the slice can be emitted as `[x; n]`.
+    fn is_repeat_array(&self) -> bool;
+}
+
+impl<T> RepeatArray for [T] {
+    default fn is_repeat_array(&self) -> bool {
+        false
+    }
+}
+
+impl<T: Copy + ExactEq> RepeatArray for [T] {
+    fn is_repeat_array(&self) -> bool {
+        is_uniform(self)
+    }
+}
+
+pub trait RepeatVec {
+    /// The segments of the slice, if any of them is a run long enough to be
+    /// worth emitting with `vec![x; n]` or `repeat(x).take(n)`.
+    fn repeat_vec_segments(&self) -> Option<Vec<Segment>>;
+}
+
+impl<T> RepeatVec for [T] {
+    default fn repeat_vec_segments(&self) -> Option<Vec<Segment>> {
+        None
+    }
+}
+
+impl<T: Clone + ExactEq> RepeatVec for [T] {
+    default fn repeat_vec_segments(&self) -> Option<Vec<Segment>> {
+        find_segments(self, MIN_RUN_LEN)
+    }
+}
+
+impl RepeatVec for [u8] {
+    fn repeat_vec_segments(&self) -> Option<Vec<Segment>> {
+        if is_uniform(self) {
+            return Some(vec![
+                Segment {
+                    start: 0,
+                    len: self.len(),
+                    repeated: true
+                },
+            ]);
+        }
+        find_segments(self, MIN_BYTE_RUN_LEN)
+    }
+}
+
+pub fn append_repeat_array<T>(ctx: &mut Context, items: &[T])
+where
+    T: ToCustomTokens
+{
+    ctx.trace_note("repeated array");
+    ctx.append("[");
+    ctx.tokenize_element(0, &items[0]);
+    ctx.append(";");
+    ctx.append(items.len().to_string());
+    ctx.append("]");
+}
+
+/// Emits `vec![x; n]` for a single run, or otherwise chains the long runs as
+/// `repeat(x).take(n)` with the short stretches in between. Iterator methods
+/// need the prelude, so without it only the single run form is available.
+pub fn append_repeat_vec<T>(ctx: &mut Context, items: &[T], segments: &[Segment])
+where
+    T: ToCustomTokens
+{
+    ctx.trace_note("repeated runs");
+    if segments.len() == 1 {
+        ctx.append_prelude_path("vec!", "::std::vec!");
+        ctx.append("[");
+        ctx.tokenize_element(0, &items[0]);
+        ctx.append(";");
+        ctx.append(items.len().to_string());
+        ctx.append("]");
+        return;
+    }
+    if ctx.options().no_implicit_prelude {
+        items.to_vec_tokens(ctx);
+        return;
+    }
+
+    segments.iter().enumerate().for_each(|(index, segment)| {
+        if index == 0 {
+            ctx.append("::std::iter::IntoIterator::into_iter");
+        } else {
+            ctx.append(".");
+            ctx.append("chain");
+        }
+        ctx.append("(");
+        if segment.repeated {
+            ctx.append("::std::iter::repeat");
+            ctx.append("(");
+            ctx.tokenize_element(segment.start, &items[segment.start]);
+            ctx.append(")");
+            ctx.append(".");
+            ctx.append("take");
+            ctx.append("(");
+            ctx.append(segment.len.to_string());
+            ctx.append(")");
+        } else {
+            items[segment.start..segment.start + segment.len].to_vec_tokens(ctx);
+        }
+        ctx.append(")");
+    });
+    ctx.append(".");
+    ctx.append("collect");
+    ctx.append("::");
+    ctx.append("<");
+    ctx.append("Vec");
+    ctx.append("<");
+    ctx.append("_");
+    ctx.append(">");
+    ctx.append(">");
+    ctx.append("(");
+    ctx.append(")");
+}
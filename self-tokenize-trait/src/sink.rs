@@ -0,0 +1,152 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::io;
+
+#[cfg(feature = "proc-macro2")]
+use proc_macro2::{LexError, TokenStream};
+use quote::Tokens;
+
+/// Destination for the tokens emitted by a `Context`. Tokens arrive one at a
+/// time, in order, and are separated the same way `quote::Tokens` separates them.
+pub trait TokenSink {
+    fn append_token(&mut self, token: &str);
+
+    /// Appends a single token whose text arrives in pieces, such as a long
+    /// literal. Sinks that can write the pieces as they come never hold the
+    /// whole token; by default they are joined first.
+    fn append_token_parts(&mut self, parts: &mut dyn Iterator<Item = String>) {
+        let token: String = parts.collect();
+        self.append_token(&token);
+    }
+}
+
+impl TokenSink for Tokens {
+    fn append_token(&mut self, token: &str) {
+        self.append(token);
+    }
+}
+
+impl TokenSink for String {
+    fn append_token(&mut self, token: &str) {
+        if !self.is_empty() && !token.is_empty() {
+            self.push(' ');
+        }
+        self.push_str(token);
+    }
+
+    fn append_token_parts(&mut self, parts: &mut dyn Iterator<Item = String>) {
+        if !self.is_empty() {
+            self.push(' ');
+        }
+        parts.for_each(|part| self.push_str(&part));
+    }
+}
+
+/// Drops all tokens, for when only the size of the output matters.
+pub struct Discard;
+
+impl TokenSink for Discard {
+    fn append_token(&mut self, _: &str) {}
+}
+
+/// Streams tokens to any `io::Write`, e.g. a `BufWriter<File>` in a build script,
+/// so that the output never has to be held in memory.
+pub struct WriteSink<W: io::Write> {
+    writer: W,
+    is_empty: bool,
+    error: Option<io::Error>
+}
+
+impl<W: io::Write> WriteSink<W> {
+    pub fn new(writer: W) -> Self {
+        WriteSink {
+            writer,
+            is_empty: true,
+            error: None
+        }
+    }
+
+    /// Flushes the writer and returns it, or the first error it reported.
+    /// Nothing is written after an error.
+    pub fn finish(mut self) -> io::Result<W> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => self.writer.flush().map(|_| self.writer)
+        }
+    }
+}
+
+impl<W: io::Write> TokenSink for WriteSink<W> {
+    fn append_token(&mut self, token: &str) {
+        if self.error.is_some() || token.is_empty() {
+            return;
+        }
+        let result = if self.is_empty {
+            self.writer.write_all(token.as_bytes())
+        } else {
+            self.writer
+                .write_all(b" ")
+                .and_then(|_| self.writer.write_all(token.as_bytes()))
+        };
+        self.is_empty = false;
+        self.error = result.err();
+    }
+
+    fn append_token_parts(&mut self, parts: &mut dyn Iterator<Item = String>) {
+        if self.error.is_some() {
+            return;
+        }
+        if !self.is_empty {
+            if let Err(error) = self.writer.write_all(b" ") {
+                self.error = Some(error);
+                return;
+            }
+        }
+        self.is_empty = false;
+        for part in parts {
+            if let Err(error) = self.writer.write_all(part.as_bytes()) {
+                self.error = Some(error);
+                return;
+            }
+        }
+    }
+}
+
+/// Builds a `proc_macro2::TokenStream`. Tokens may leave delimiters open until
+/// later ones close them, so the text is only lexed by `into_token_stream`.
+#[cfg(feature = "proc-macro2")]
+#[derive(Debug, Default)]
+pub struct TokenStreamSink {
+    source: String
+}
+
+#[cfg(feature = "proc-macro2")]
+impl TokenStreamSink {
+    pub fn new() -> Self {
+        TokenStreamSink::default()
+    }
+
+    pub fn into_token_stream(self) -> Result<TokenStream, LexError> {
+        self.source.parse()
+    }
+}
+
+#[cfg(feature = "proc-macro2")]
+impl TokenSink for TokenStreamSink {
+    fn append_token(&mut self, token: &str) {
+        self.source.append_token(token);
+    }
+
+    fn append_token_parts(&mut self, parts: &mut dyn Iterator<Item = String>) {
+        self.source.append_token_parts(parts);
+    }
+}
@@ -0,0 +1,181 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use context::{Context, Options};
+use error::{Error, ErrorKind};
+use items::{append_associated_const, append_constructor, append_default_impl, append_lazy_static, append_thread_local};
+use pretty::{pretty_print, PrettyOptions};
+use ToCustomTokens;
+
+/// Lints that generated code tends to trip, e.g. `unused_parens` for negative
+/// numbers or `clippy::unreadable_literal` for long ones.
+const ALLOWED_LINTS: &str = "dead_code, non_upper_case_globals, unused_parens";
+const ALLOWED_CLIPPY_LINTS: &str = "clippy::all, clippy::pedantic";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    /// `const NAME: T = value;`
+    Const,
+    /// `static NAME: T = value;`
+    Static,
+    /// A `static` initialized on first access. See `append_lazy_static`.
+    Lazy,
+    /// A `thread_local!` static. See `append_thread_local`.
+    ThreadLocal,
+    /// `impl Default for T`, which ignores the name and visibility.
+    DefaultImpl,
+    /// `fn NAME() -> T`. See `append_constructor`.
+    Fn,
+    /// `impl T { const NAME: Self = value; }`
+    AssociatedConst
+}
+
+/// A Rust source file of named values, written from a build script and pulled
+/// into the crate with `include!(concat!(env!("OUT_DIR"), "/name.rs"))`.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    /// Used for every value. `Const`, `Static` and `AssociatedConst` items also
+    /// set `constant`.
+    pub options: Options,
+    /// Layout of each item. `None` writes the tokens as they are, on a single
+    /// line, which is faster for very large values.
+    pub pretty: Option<PrettyOptions>,
+    items: Vec<String>,
+    inputs: Vec<PathBuf>,
+    error: Option<Error>
+}
+
+impl SourceFile {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        SourceFile {
+            path: path.into(),
+            options: Options::default(),
+            pretty: Some(PrettyOptions::default()),
+            items: vec![],
+            inputs: vec![],
+            error: None
+        }
+    }
+
+    /// A file named `file_name` in `OUT_DIR`.
+    ///
+    /// # Panics
+    ///
+    /// If `OUT_DIR` is not set, i.e. outside of a build script.
+    pub fn in_out_dir(file_name: &str) -> Self {
+        let out_dir = env::var_os("OUT_DIR").expect("OUT_DIR is only set for build scripts");
+        SourceFile::new(PathBuf::from(out_dir).join(file_name))
+    }
+
+    /// Registers a file the values were read from, so that cargo reruns the
+    /// build script when it changes.
+    pub fn input<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.inputs.push(path.into());
+        self
+    }
+
+    /// Adds an item named `name` with visibility `vis`, e.g. `pub(crate)` or
+    /// an empty string. Errors are reported by `write`, with the path of the
+    /// offending field prefixed by `name`.
+    pub fn add<T>(&mut self, kind: ItemKind, vis: &str, name: &str, value: &T) -> &mut Self
+    where
+        T: ToCustomTokens
+    {
+        let constant = match kind {
+            ItemKind::Const | ItemKind::Static | ItemKind::AssociatedConst => true,
+            ItemKind::Lazy | ItemKind::ThreadLocal | ItemKind::DefaultImpl | ItemKind::Fn => self.options.constant
+        };
+        let options = Options {
+            constant,
+            ..self.options.clone()
+        };
+        let mut code = String::new();
+        let mut ctx = Context::with_options(&mut code, options);
+        match kind {
+            ItemKind::Lazy => append_lazy_static(&mut ctx, vis, name, value),
+            ItemKind::ThreadLocal => append_thread_local(&mut ctx, vis, name, value),
+            ItemKind::DefaultImpl => append_default_impl(&mut ctx, value),
+            ItemKind::Fn => append_constructor(&mut ctx, vis, name, value),
+            ItemKind::AssociatedConst => append_associated_const(&mut ctx, vis, name, value),
+            ItemKind::Const | ItemKind::Static => {
+                if !vis.is_empty() {
+                    ctx.append(vis);
+                }
+                ctx.append(if kind == ItemKind::Const { "const" } else { "static" });
+                ctx.append(name);
+                ctx.append(":");
+                ctx.append_item_type::<T>();
+                ctx.append("=");
+                ctx.tokenize(value);
+                ctx.append(";");
+            }
+        }
+        if let Err(error) = ctx.finish() {
+            if self.error.is_none() {
+                self.error = Some(Error::new(format!("{}{}", name, error.path()), error.kind().clone()));
+            }
+        }
+        self.items.push(code);
+        self
+    }
+
+    /// The contents of the file: a header, then each item, pretty-printed unless
+    /// `pretty` is `None`, with `#[allow]` attributes for lints that do not
+    /// apply to generated code.
+    pub fn to_source(&self) -> Result<String, Error> {
+        if let Some(ref error) = self.error {
+            return Err(error.clone());
+        }
+        let mut source = "// @generated by self-tokenize-trait. Do not edit.\n".to_string();
+        if !self.inputs.is_empty() {
+            let inputs: Vec<String> = self.inputs.iter().map(|input| input.display().to_string()).collect();
+            source.push_str(&format!("// Inputs: {}\n", inputs.join(", ")));
+        }
+        let lints = if self.options.supports(1, 31) {
+            format!("{}, {}", ALLOWED_LINTS, ALLOWED_CLIPPY_LINTS)
+        } else {
+            ALLOWED_LINTS.to_string()
+        };
+        for item in &self.items {
+            source.push_str(&format!("\n#[allow({})]\n", lints));
+            match self.pretty {
+                Some(pretty) => source.push_str(&pretty_print(item, pretty)),
+                None => source.push_str(item)
+            }
+            source.push('\n');
+        }
+        Ok(source)
+    }
+
+    /// Writes the file, unless it is unchanged so that its dependents are not
+    /// rebuilt, and prints `cargo:rerun-if-changed` for the inputs.
+    pub fn write(&self) -> Result<(), Error> {
+        let source = self.to_source()?;
+        if fs::read_to_string(&self.path).ok().as_ref() != Some(&source) {
+            fs::write(&self.path, source).map_err(|error| {
+                let kind = ErrorKind::Io {
+                    file: self.path.clone(),
+                    message: error.to_string()
+                };
+                Error::new(String::new(), kind)
+            })?;
+        }
+        self.inputs
+            .iter()
+            .for_each(|input| println!("cargo:rerun-if-changed={}", input.display()));
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::ops::Range;
+
+use context::Options;
+use pretty::{layout, PrettyOptions};
+use trace::{trace, Trace, TraceNode};
+use ToCustomTokens;
+
+/// Maps byte ranges of the output of a value back to the fields they were
+/// emitted for. The offsets are those of `Tokens::to_string()` and of a
+/// `WriteSink` given the same options.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceMap {
+    pub code: String,
+    /// Every value in the output, outer values before the values inside them.
+    pub entries: Vec<SourceMapEntry>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceMapEntry {
+    pub span: Range<usize>,
+    /// E.g. `.baz[1].unit`, empty for the value itself.
+    pub path: String,
+    pub type_name: &'static str
+}
+
+/// Tokenizes `value` into a string along with its `SourceMap`.
+pub fn source_map<T>(value: &T, options: Options) -> SourceMap
+where
+    T: ?Sized + ToCustomTokens
+{
+    SourceMap::from(trace(value, options))
+}
+
+impl SourceMap {
+    /// The innermost value whose output contains `offset`.
+    pub fn entry_at(&self, offset: usize) -> Option<&SourceMapEntry> {
+        self.entries.iter().rev().find(|entry| entry.span.contains(&offset))
+    }
+
+    /// The innermost value at a position reported by rustc, with lines and
+    /// columns counted from 1 and columns in characters.
+    pub fn field_at(&self, line: usize, column: usize) -> Option<&SourceMapEntry> {
+        self.offset_of(line, column).and_then(|offset| self.entry_at(offset))
+    }
+
+    /// The same map for `pretty_print(code, options)`, to look up positions
+    /// in pretty-printed output.
+    pub fn pretty(&self, options: PrettyOptions) -> SourceMap {
+        let layout = layout(&self.code, options);
+        let entries = self.entries
+            .iter()
+            .map(|entry| SourceMapEntry {
+                span: layout.map_span(entry.span.clone()),
+                path: entry.path.clone(),
+                type_name: entry.type_name
+            })
+            .collect();
+        SourceMap {
+            code: layout.code,
+            entries
+        }
+    }
+
+    /// Byte offset of a 1-based line and column of `code`.
+    pub fn offset_of(&self, line: usize, column: usize) -> Option<usize> {
+        if line == 0 || column == 0 {
+            return None;
+        }
+        let line_start = match line {
+            1 => 0,
+            _ => self.code.match_indices('\n').nth(line - 2)?.0 + 1
+        };
+        let line_end = self.code[line_start..].find('\n').map_or(self.code.len(), |end| line_start + end);
+        self.code[line_start..line_end]
+            .char_indices()
+            .nth(column - 1)
+            .map(|(offset, _)| line_start + offset)
+    }
+}
+
+impl From<Trace> for SourceMap {
+    fn from(trace: Trace) -> Self {
+        let mut entries = vec![];
+        add_entries(&mut entries, trace.root);
+        SourceMap {
+            code: trace.output,
+            entries
+        }
+    }
+}
+
+fn add_entries(entries: &mut Vec<SourceMapEntry>, node: TraceNode) {
+    entries.push(SourceMapEntry {
+        span: node.span,
+        path: node.path,
+        type_name: node.type_name
+    });
+    node.children.into_iter().for_each(|child| add_entries(entries, child));
+}
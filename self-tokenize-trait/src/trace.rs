@@ -0,0 +1,142 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::any::type_name;
+use std::fmt;
+use std::ops::Range;
+
+use context::{Context, Options};
+use ToCustomTokens;
+
+/// Fragments longer than this are elided in the middle when a trace is printed.
+const MAX_FRAGMENT_LEN: usize = 72;
+
+/// The output of a value, with the impl that produced each part of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    pub output: String,
+    pub root: TraceNode
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceNode {
+    pub type_name: &'static str,
+    /// See `ToCustomTokens::impl_name`.
+    pub impl_name: &'static str,
+    /// Field path of the value, e.g. `.baz[1].unit`.
+    pub path: String,
+    /// Strategies the impl picked, e.g. `compact numbers`.
+    pub notes: Vec<&'static str>,
+    /// Byte range of `output` emitted for the value, including its children.
+    pub span: Range<usize>,
+    pub children: Vec<TraceNode>
+}
+
+/// Tokenizes `value`, recording which type and impl emitted each span.
+pub fn trace<T>(value: &T, options: Options) -> Trace
+where
+    T: ?Sized + ToCustomTokens
+{
+    let mut output = String::new();
+    let root = Context::with_options(&mut output, options).trace(|ctx| ctx.tokenize(value));
+    Trace { output, root }
+}
+
+impl TraceNode {
+    /// The innermost value whose span contains `offset`.
+    pub fn find(&self, offset: usize) -> Option<&TraceNode> {
+        if !self.span.contains(&offset) {
+            return None;
+        }
+        self.children
+            .iter()
+            .filter_map(|child| child.find(offset))
+            .next()
+            .or(Some(self))
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_node(f, &self.output, &self.root, 0)
+    }
+}
+
+fn write_node(f: &mut fmt::Formatter, output: &str, node: &TraceNode, depth: usize) -> fmt::Result {
+    write!(f, "{:indent$}", "", indent = depth * 2)?;
+    if !node.path.is_empty() {
+        write!(f, "{} ", node.path)?;
+    }
+    write!(f, "{} via {}", node.type_name, node.impl_name)?;
+    if !node.notes.is_empty() {
+        write!(f, " ({})", node.notes.join(", "))?;
+    }
+    writeln!(f, " [{}..{}]: {}", node.span.start, node.span.end, elide(&output[node.span.clone()]))?;
+    node.children
+        .iter()
+        .try_for_each(|child| write_node(f, output, child, depth + 1))
+}
+
+fn elide(fragment: &str) -> String {
+    if fragment.len() <= MAX_FRAGMENT_LEN {
+        return fragment.to_string();
+    }
+    let half = MAX_FRAGMENT_LEN / 2;
+    let start = (0..=half).rev().find(|&i| fragment.is_char_boundary(i)).unwrap();
+    let end = (fragment.len() - half..fragment.len()).find(|&i| fragment.is_char_boundary(i)).unwrap();
+    format!("{} … {}", &fragment[..start], &fragment[end..])
+}
+
+/// Builds the `TraceNode`s of the values a `Context` tokenizes.
+#[derive(Debug, Default)]
+pub struct Tracer {
+    // Values being tokenized, outermost first.
+    open: Vec<TraceNode>,
+    root: Option<TraceNode>
+}
+
+impl Tracer {
+    pub fn begin<T>(&mut self, path: String)
+    where
+        T: ?Sized + ToCustomTokens
+    {
+        let node = TraceNode {
+            type_name: type_name::<T>(),
+            impl_name: T::impl_name(),
+            path,
+            notes: vec![],
+            span: 0..0,
+            children: vec![]
+        };
+        self.open.push(node);
+    }
+
+    pub fn note(&mut self, note: &'static str) {
+        if let Some(node) = self.open.last_mut() {
+            if !node.notes.contains(&note) {
+                node.notes.push(note);
+            }
+        }
+    }
+
+    pub fn end(&mut self, span: Range<usize>) {
+        let mut node = self.open.pop().unwrap();
+        node.span = span;
+        match self.open.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => self.root = Some(node)
+        }
+    }
+
+    pub fn into_root(self) -> TraceNode {
+        self.root.unwrap()
+    }
+}